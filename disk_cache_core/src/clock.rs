@@ -0,0 +1,30 @@
+//! The current time, as cache expiry sees it. With the `test-clock` feature, it can be moved forward with [`advance`],
+//! so that tests can expire entries without sleeping.
+
+use chrono::{DateTime, Utc};
+#[cfg(feature = "test-clock")]
+use std::sync::atomic::{AtomicI64, Ordering};
+
+/// Milliseconds added to the system time by [`advance`].
+#[cfg(feature = "test-clock")]
+static OFFSET_MS: AtomicI64 = AtomicI64::new(0);
+
+/// Returns the time entries are written at and compared against, the system time unless moved forward by [`advance`].
+pub fn now() -> DateTime<Utc> {
+    let now = Utc::now();
+    #[cfg(feature = "test-clock")]
+    let now = now + chrono::Duration::milliseconds(OFFSET_MS.load(Ordering::SeqCst));
+    now
+}
+
+/// Moves [`now`] forward by `by`, for every thread of the process.
+#[cfg(feature = "test-clock")]
+pub fn advance(by: std::time::Duration) {
+    OFFSET_MS.fetch_add(by.as_millis() as i64, Ordering::SeqCst);
+}
+
+/// Moves [`now`] back to the system time.
+#[cfg(feature = "test-clock")]
+pub fn reset() {
+    OFFSET_MS.store(0, Ordering::SeqCst);
+}
@@ -0,0 +1,273 @@
+//! The bytes of cache entries: the serde formats and compressions they are written with, and the stamp recording when
+//! they were written.
+
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::io::{self, Read, Write};
+
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::{clock, CacheError};
+
+/// The serde formats entries can be written in, with `format`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    /// JSON written indented, with `pretty = true`. It is read like any other JSON.
+    PrettyJson,
+    Bincode,
+    Cbor,
+    Ron,
+    MessagePack,
+    Toml,
+    Yaml,
+}
+
+impl Format {
+    /// The format of the entry at `path`, named by the first extension of its file name as in `data.bin.gz`, for entries
+    /// read with `format = "auto"`.
+    pub fn of_path(path: &str) -> Result<Format, CacheError> {
+        match path.rsplit('/').next().and_then(|name| name.split('.').nth(1)) {
+            Some("json") => Ok(Format::Json),
+            Some("bin") => Ok(Format::Bincode),
+            Some("cbor") => Ok(Format::Cbor),
+            Some("ron") => Ok(Format::Ron),
+            Some("msgpack") => Ok(Format::MessagePack),
+            Some("toml") => Ok(Format::Toml),
+            Some("yaml") => Ok(Format::Yaml),
+            extension => Err(CacheError::Deserialize(format!("unknown cache file extension {:?}", extension).into())),
+        }
+    }
+
+    pub fn serialize<T: Serialize + ?Sized>(self, value: &T) -> Result<Vec<u8>, CacheError> {
+        fn failed(e: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> CacheError {
+            CacheError::Serialize(e.into())
+        }
+        match self {
+            Format::Json => serde_json::to_vec(value).map_err(failed),
+            Format::PrettyJson => serde_json::to_vec_pretty(value).map_err(failed),
+            Format::Bincode => bincode::serialize(value).map_err(failed),
+            Format::Cbor => {
+                let mut data = Vec::new();
+                ciborium::into_writer(value, &mut data).map(|()| data).map_err(failed)
+            },
+            Format::Ron => ron::ser::to_string(value).map(String::into_bytes).map_err(failed),
+            Format::MessagePack => rmp_serde::to_vec(value).map_err(failed),
+            Format::Toml => toml::to_string(value).map(String::into_bytes).map_err(failed),
+            Format::Yaml => serde_yaml::to_string(value).map(String::into_bytes).map_err(failed),
+        }
+    }
+
+    /// With the `simd-json` feature, JSON is parsed with `simd-json`, which uses `data` as scratch space.
+    pub fn deserialize<T: DeserializeOwned>(self, data: Vec<u8>) -> Result<T, CacheError> {
+        fn failed(e: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> CacheError {
+            CacheError::Deserialize(e.into())
+        }
+        match self {
+            #[cfg(feature = "simd-json")]
+            Format::Json | Format::PrettyJson => {
+                let mut data = data;
+                simd_json::serde::from_slice(&mut data).map_err(failed)
+            },
+            #[cfg(not(feature = "simd-json"))]
+            Format::Json | Format::PrettyJson => serde_json::from_slice(&data).map_err(failed),
+            Format::Bincode => bincode::deserialize(&data).map_err(failed),
+            Format::Cbor => ciborium::from_reader(data.as_slice()).map_err(failed),
+            Format::Ron => std::str::from_utf8(&data).map_err(failed).and_then(|data| ron::de::from_str(data).map_err(failed)),
+            Format::MessagePack => rmp_serde::from_slice(&data).map_err(failed),
+            Format::Toml => std::str::from_utf8(&data).map_err(failed).and_then(|data| toml::from_str(data).map_err(failed)),
+            Format::Yaml => serde_yaml::from_slice(&data).map_err(failed),
+        }
+    }
+
+    /// Like [`Format::deserialize`], but a value that does not deserialize as `T`, with `read_as`, is deserialized as
+    /// `Old` and converted. The error is the one of `T`.
+    pub fn deserialize_as<T, Old>(self, data: Vec<u8>) -> Result<T, CacheError>
+    where
+        T: DeserializeOwned + From<Old>,
+        Old: DeserializeOwned,
+    {
+        match self.deserialize::<T>(data.clone()) {
+            Ok(value) => Ok(value),
+            Err(e) => self.deserialize::<Old>(data).map(T::from).map_err(|_| e),
+        }
+    }
+}
+
+/// The compression applied to the serialized bytes, independently of the format, with `compress`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    /// With its level, from 0 to 9.
+    Gzip(u32),
+    /// With its level, from 1 to 22.
+    Zstd(i32),
+}
+
+impl Compression {
+    /// Compresses `data`. With `min_bytes`, from `compress_min_bytes`, a leading byte tells whether the rest is
+    /// compressed, and data shorter than that is not.
+    pub fn compress(self, min_bytes: Option<usize>, data: Vec<u8>) -> io::Result<Vec<u8>> {
+        match min_bytes {
+            Some(min_bytes) if data.len() < min_bytes => Ok([vec![0u8], data].concat()),
+            Some(_) => self.compress(None, data).map(|compressed| [vec![1u8], compressed].concat()),
+            None => match self {
+                Compression::None => Ok(data),
+                Compression::Gzip(level) => {
+                    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::new(level));
+                    encoder.write_all(&data).and_then(|()| encoder.finish())
+                },
+                Compression::Zstd(level) => zstd::encode_all(data.as_slice(), level),
+            },
+        }
+    }
+
+    /// Decompresses data written by [`Compression::compress`], with `flagged` telling whether it was given a
+    /// `min_bytes`.
+    pub fn decompress(self, flagged: bool, data: Vec<u8>) -> Result<Vec<u8>, CacheError> {
+        if flagged {
+            return match data.split_first() {
+                Some((0, data)) => Ok(data.to_vec()),
+                Some((1, data)) => self.decompress(false, data.to_vec()),
+                Some((flag, _)) => Err(CacheError::Deserialize(format!("unknown compression flag {}", flag).into())),
+                None => Err(CacheError::Deserialize("cache entry is missing its compression flag".into())),
+            };
+        }
+        match self {
+            Compression::None => Ok(data),
+            Compression::Gzip(_) => {
+                let mut decompressed = Vec::new();
+                flate2::read::GzDecoder::new(data.as_slice())
+                    .read_to_end(&mut decompressed)
+                    .map(|_| decompressed)
+                    .map_err(|e| CacheError::Deserialize(e.into()))
+            },
+            Compression::Zstd(_) => zstd::decode_all(data.as_slice()).map_err(|e| CacheError::Deserialize(e.into())),
+        }
+    }
+}
+
+/// When an entry was written, stored in it unless `timestamp = "mtime"`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stamp {
+    /// Milliseconds since the Unix epoch.
+    pub written_at: i64,
+    /// With `invalidate_jitter`, the seconds added to the invalidate rate of this entry.
+    pub jitter: Option<i64>,
+}
+
+impl Stamp {
+    /// Stamps an entry written now. With `max_jitter`, it draws a jitter of up to that many seconds.
+    pub fn now(max_jitter: Option<i64>) -> Stamp {
+        let jitter = max_jitter.map(|max_jitter| (RandomState::new().hash_one(()) % (max_jitter as u64 + 1)) as i64);
+        Stamp { written_at: clock::now().timestamp_millis(), jitter }
+    }
+
+    /// The write time pushed back by the jitter, so that every expiry check accounts for it.
+    pub fn last_written(&self) -> DateTime<Utc> {
+        DateTime::<Utc>::from_timestamp_millis(self.written_at).unwrap_or_default() + chrono::Duration::seconds(self.jitter.unwrap_or(0))
+    }
+}
+
+/// What is written for an entry whose write time is embedded in it.
+#[derive(Serialize, Deserialize)]
+struct Envelope<T> {
+    written_at: i64,
+    payload: T,
+}
+
+/// An [`Envelope`] with `invalidate_jitter`.
+#[derive(Serialize, Deserialize)]
+struct JitteredEnvelope<T> {
+    written_at: i64,
+    jitter: i64,
+    payload: T,
+}
+
+/// An [`Envelope`] without its payload, which is left unread.
+#[derive(Deserialize)]
+struct Timestamp {
+    written_at: i64,
+}
+
+/// A [`JitteredEnvelope`] without its payload, which is left unread.
+#[derive(Deserialize)]
+struct JitteredTimestamp {
+    written_at: i64,
+    jitter: i64,
+}
+
+/// Serializes `payload` in `format`, in the envelope recording `stamp`.
+pub fn encode<T: Serialize + ?Sized>(format: Format, stamp: Stamp, payload: &T) -> Result<Vec<u8>, CacheError> {
+    match stamp.jitter {
+        Some(jitter) => format.serialize(&JitteredEnvelope { written_at: stamp.written_at, jitter, payload }),
+        None => format.serialize(&Envelope { written_at: stamp.written_at, payload }),
+    }
+}
+
+/// Deserializes an entry written by [`encode`], with `jittered` telling whether its stamp holds a jitter.
+pub fn decode<T: DeserializeOwned>(format: Format, jittered: bool, data: Vec<u8>) -> Result<(Stamp, T), CacheError> {
+    if jittered {
+        let envelope: JitteredEnvelope<T> = format.deserialize(data)?;
+        Ok((Stamp { written_at: envelope.written_at, jitter: Some(envelope.jitter) }, envelope.payload))
+    } else {
+        let envelope: Envelope<T> = format.deserialize(data)?;
+        Ok((Stamp { written_at: envelope.written_at, jitter: None }, envelope.payload))
+    }
+}
+
+/// Like [`decode`], but an entry that does not decode as `T`, with `read_as`, is decoded as `Old` and converted. `T` is
+/// tried first, as serde skips unknown fields, so that a new entry could decode as the old type and lose some of them.
+pub fn decode_as<T, Old>(format: Format, jittered: bool, data: Vec<u8>) -> Result<(Stamp, T), CacheError>
+where
+    T: DeserializeOwned + From<Old>,
+    Old: DeserializeOwned,
+{
+    match decode::<T>(format, jittered, data.clone()) {
+        Ok(decoded) => Ok(decoded),
+        Err(e) => decode::<Old>(format, jittered, data).map(|(stamp, old)| (stamp, T::from(old))).map_err(|_| e),
+    }
+}
+
+/// Reads only the stamp of an entry written by [`encode`].
+pub fn decode_stamp(format: Format, jittered: bool, data: Vec<u8>) -> Result<Stamp, CacheError> {
+    // MessagePack writes structs as arrays, which must be read to the end, so the payload is skipped rather than left
+    if format == Format::MessagePack {
+        return decode::<serde::de::IgnoredAny>(format, jittered, data).map(|(stamp, _)| stamp);
+    }
+    if jittered {
+        let timestamp: JitteredTimestamp = format.deserialize(data)?;
+        Ok(Stamp { written_at: timestamp.written_at, jitter: Some(timestamp.jitter) })
+    } else {
+        let timestamp: Timestamp = format.deserialize(data)?;
+        Ok(Stamp { written_at: timestamp.written_at, jitter: None })
+    }
+}
+
+/// Puts `stamp` in front of `payload`, for the encodings of `serialize_with` and of a cached `Vec<u8>`, which only
+/// handle the payload: the write time and then the jitter are stored as 8 little-endian bytes each.
+pub fn with_stamp(stamp: Stamp, payload: &[u8]) -> Vec<u8> {
+    let mut data = stamp.written_at.to_le_bytes().to_vec();
+    if let Some(jitter) = stamp.jitter {
+        data.extend(jitter.to_le_bytes());
+    }
+    data.extend_from_slice(payload);
+    data
+}
+
+/// Splits the stamp put in front of an entry by [`with_stamp`] from its payload.
+pub fn split_stamp(jittered: bool, data: &[u8]) -> Result<(Stamp, &[u8]), CacheError> {
+    let Some((written_at, payload)) = data.split_first_chunk::<8>() else {
+        return Err(CacheError::Deserialize("cache entry is missing its write time".into()));
+    };
+    let stamp = Stamp { written_at: i64::from_le_bytes(*written_at), jitter: None };
+    if !jittered {
+        return Ok((stamp, payload));
+    }
+    let Some((jitter, payload)) = payload.split_first_chunk::<8>() else {
+        return Err(CacheError::Deserialize("cache entry is missing its jitter".into()));
+    };
+    Ok((Stamp { jitter: Some(i64::from_le_bytes(*jitter)), ..stamp }, payload))
+}
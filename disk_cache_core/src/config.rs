@@ -0,0 +1,24 @@
+//! Cache policy shared by several cached functions.
+
+/// Defaults for the attributes of the cached functions naming it with `config = PATH`, where `PATH` is a `const` or
+/// `static` of this type. An attribute given on the function itself overrides the corresponding field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheConfig {
+    /// The directory the entries are stored below, in place of `cache_root`. It is read at runtime, so that it cannot
+    /// interpolate arguments and `~` is not expanded; entries are told apart by the function's name and `key = "args"`.
+    pub cache_root: &'static str,
+    /// The time in seconds after which an entry is invalid, in place of `invalidate_rate`.
+    pub invalidate_rate: u64,
+}
+
+impl CacheConfig {
+    /// A configuration storing entries below `cache_root`, which expire after an hour like with the defaults.
+    pub const fn new(cache_root: &'static str) -> Self {
+        CacheConfig { cache_root, invalidate_rate: 3600 }
+    }
+
+    /// Sets the time in seconds after which an entry is invalid.
+    pub const fn invalidate_rate(self, seconds: u64) -> Self {
+        CacheConfig { invalidate_rate: seconds, ..self }
+    }
+}
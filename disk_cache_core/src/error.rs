@@ -0,0 +1,52 @@
+//! The error returned by cached functions when the cache itself fails.
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+/// A failure of the cache layer, as opposed to an error returned by the cached function.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum CacheError {
+    /// Reading, writing or removing an entry failed.
+    Io(io::Error),
+    /// The value could not be encoded in the cache format.
+    Serialize(Box<dyn Error + Send + Sync>),
+    /// An entry could not be decoded, typically because it is corrupt or was written in another format.
+    Deserialize(Box<dyn Error + Send + Sync>),
+}
+
+impl fmt::Display for CacheError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CacheError::Io(e) => write!(f, "cache io error: {}", e),
+            CacheError::Serialize(e) => write!(f, "failed to serialize cache entry: {}", e),
+            CacheError::Deserialize(e) => write!(f, "failed to deserialize cache entry: {}", e),
+        }
+    }
+}
+
+impl Error for CacheError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            CacheError::Io(e) => Some(e),
+            CacheError::Serialize(e) | CacheError::Deserialize(e) => Some(&**e),
+        }
+    }
+}
+
+impl From<io::Error> for CacheError {
+    fn from(e: io::Error) -> Self {
+        CacheError::Io(e)
+    }
+}
+
+/// Errors that are not about io are reported as [`io::ErrorKind::InvalidData`].
+impl From<CacheError> for io::Error {
+    fn from(e: CacheError) -> Self {
+        match e {
+            CacheError::Io(e) => e,
+            e => io::Error::new(io::ErrorKind::InvalidData, e),
+        }
+    }
+}
@@ -0,0 +1,18 @@
+//! What happens to the cache during a call, as reported to the function named by `on_event`.
+
+use std::io;
+use std::time::Duration;
+
+/// Passed to the function named by `on_event` at each step of a cached call.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum CacheEvent<'a> {
+    /// The value was read from the cache, and was written `age` ago.
+    Hit { age: Duration },
+    /// No valid entry was found, or the lookup was bypassed, so the function runs.
+    Miss,
+    /// A computed value was written to the cache.
+    WriteOk,
+    /// Writing a computed value to the cache failed.
+    WriteErr(&'a io::Error),
+}
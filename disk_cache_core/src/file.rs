@@ -0,0 +1,317 @@
+//! Cache files: their paths, their atomic writes, and the envelope recording when they were written.
+
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::codec::{self, Format, Stamp};
+use crate::{clock, CacheError};
+
+/// Makes an interpolated value filesystem-safe, so that it always forms a single path segment: path separators and `%`
+/// are percent-encoded, and `.`/`..` are encoded as well. Values can then neither escape the cache root nor collide.
+///
+/// The value is valid on every platform, so that entries keep their path when the cache is shared: the characters
+/// Windows rejects in file names (`<>:"|?*` and control characters), a trailing `.` or space, and the first character
+/// of a reserved device name such as `CON` or `com1.txt` are percent-encoded too. A value longer than 255 bytes, the
+/// limit of most filesystems for a single segment, is cut short and followed by `%~` and a hash of the whole value,
+/// which no encoded value contains.
+pub fn sanitize_key(value: String) -> String {
+    let mut sanitized = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '/' | '\\' | '%' | '<' | '>' | ':' | '"' | '|' | '?' | '*' | '\0'..='\x1F' => {
+                sanitized.push_str(&format!("%{:02X}", c as u32))
+            },
+            c => sanitized.push(c),
+        }
+    }
+    if sanitized == "." || sanitized == ".." {
+        sanitized = sanitized.replace('.', "%2E");
+    }
+    if let Some(last @ ('.' | ' ')) = sanitized.chars().last() {
+        sanitized.pop();
+        sanitized.push_str(&format!("%{:02X}", last as u32));
+    }
+    if is_reserved_name(&sanitized) {
+        let first = sanitized.remove(0);
+        sanitized.insert_str(0, &format!("%{:02X}", first as u32));
+    }
+    if sanitized.len() > MAX_SEGMENT_LEN {
+        // FNV-1a, which is stable across Rust versions unlike the hashers of the standard library
+        let hash = value.bytes().fold(0xcbf2_9ce4_8422_2325u64, |hash, byte| (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3));
+        let mut end = MAX_SEGMENT_LEN - 18;
+        while !sanitized.is_char_boundary(end) {
+            end -= 1;
+        }
+        // an escape sequence cut in the middle would be read as another character
+        if let Some(escape) = sanitized.as_bytes()[end - 2..end].iter().position(|&byte| byte == b'%') {
+            end = end - 2 + escape;
+        }
+        sanitized.truncate(end);
+        sanitized.push_str(&format!("%~{:016x}", hash));
+    }
+    sanitized
+}
+
+/// The length in bytes of the longest file name most filesystems accept.
+const MAX_SEGMENT_LEN: usize = 255;
+
+/// Whether Windows reserves `segment` for a device, whatever its case and extension.
+fn is_reserved_name(segment: &str) -> bool {
+    let stem = segment.split('.').next().unwrap_or_default().trim_end_matches(' ').to_ascii_uppercase();
+    match stem.as_str() {
+        "CON" | "PRN" | "AUX" | "NUL" | "CONIN$" | "CONOUT$" => true,
+        _ => (stem.starts_with("COM") || stem.starts_with("LPT")) && stem.len() == 4 && stem.as_bytes()[3].is_ascii_digit(),
+    }
+}
+
+/// Writes `data` to `tmp_path` and renames it over `path`, so that a crash mid-write never leaves a truncated cache
+/// file behind. Missing parent directories are created, and the temp file is removed if the write fails.
+///
+/// The parent directory is created without checking whether it exists first: concurrent writes would all see it
+/// missing, and a directory created by another one in between is not an error.
+pub fn write_atomic(path: impl AsRef<Path>, tmp_path: impl AsRef<Path>, data: &[u8]) -> std::io::Result<()> {
+    let (path, tmp_path) = (path.as_ref(), tmp_path.as_ref());
+    let written = (|| {
+        if let Some(parent) = path.parent() {
+            ignore_existing(std::fs::create_dir_all(parent))?;
+        }
+        std::fs::write(tmp_path, data)?;
+        std::fs::rename(tmp_path, path)
+    })();
+    if written.is_err() {
+        let _ = std::fs::remove_file(tmp_path);
+    }
+    written
+}
+
+/// Like [`write_atomic`], with `tokio::fs`.
+pub async fn write_atomic_async(path: impl AsRef<Path>, tmp_path: impl AsRef<Path>, data: &[u8]) -> std::io::Result<()> {
+    let (path, tmp_path) = (path.as_ref(), tmp_path.as_ref());
+    let written = async {
+        if let Some(parent) = path.parent() {
+            ignore_existing(tokio::fs::create_dir_all(parent).await)?;
+        }
+        tokio::fs::write(tmp_path, data).await?;
+        tokio::fs::rename(tmp_path, path).await
+    }
+    .await;
+    if written.is_err() {
+        let _ = tokio::fs::remove_file(tmp_path).await;
+    }
+    written
+}
+
+/// Sets the mtime of the file at `path` to now, with `touch_on_hit`, along with that of `entry_dir` with `max_entries`,
+/// as eviction goes by it. Failures are ignored, as the entry was read anyway.
+pub fn touch(path: impl AsRef<Path>, entry_dir: Option<&str>) {
+    let now = std::time::SystemTime::now();
+    if let Ok(file) = std::fs::File::options().write(true).open(path) {
+        let _ = file.set_modified(now);
+    }
+    if let Some(Ok(dir)) = entry_dir.map(std::fs::File::open) {
+        let _ = dir.set_modified(now);
+    }
+}
+
+fn ignore_existing(created: std::io::Result<()>) -> std::io::Result<()> {
+    match created {
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => Ok(()),
+        created => created,
+    }
+}
+
+/// A temp file next to `path`, unique within this process, for [`write_atomic`].
+pub fn tmp_path(path: impl AsRef<Path>) -> PathBuf {
+    static WRITE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let write_id = WRITE_COUNTER.fetch_add(1, Ordering::Relaxed);
+    let mut tmp_path = path.as_ref().as_os_str().to_owned();
+    tmp_path.push(format!(".tmp.{}.{}", std::process::id(), write_id));
+    PathBuf::from(tmp_path)
+}
+
+/// Decodes an entry, which is `None` once it is expired.
+fn decode<T: DeserializeOwned>(data: Vec<u8>, ttl: Duration) -> Result<Option<T>, CacheError> {
+    let (stamp, payload) = codec::decode(Format::Json, false, data)?;
+    let age_ms = clock::now().timestamp_millis() - stamp.written_at;
+    Ok((age_ms >= 0 && (age_ms as u128) < ttl.as_millis()).then_some(payload))
+}
+
+fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, CacheError> {
+    codec::encode(Format::Json, Stamp::now(None), value)
+}
+
+/// Reads the value cached in the JSON entry at `path`, provided that it was written less than `ttl` ago. Missing,
+/// expired and corrupt entries are all `None`, and corrupt ones are removed.
+pub fn read_if_valid<T: DeserializeOwned>(path: impl AsRef<Path>, ttl: Duration) -> Result<Option<T>, CacheError> {
+    let path = path.as_ref();
+    let data = match std::fs::read(path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    match decode(data, ttl) {
+        Ok(result) => Ok(result),
+        Err(_) => {
+            let _ = std::fs::remove_file(path);
+            Ok(None)
+        }
+    }
+}
+
+/// Like [`read_if_valid`], with `tokio::fs`.
+pub async fn read_if_valid_async<T: DeserializeOwned>(path: impl AsRef<Path>, ttl: Duration) -> Result<Option<T>, CacheError> {
+    let path = path.as_ref();
+    let data = match tokio::fs::read(path).await {
+        Ok(data) => data,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    match decode(data, ttl) {
+        Ok(result) => Ok(result),
+        Err(_) => {
+            let _ = tokio::fs::remove_file(path).await;
+            Ok(None)
+        }
+    }
+}
+
+/// Writes `value` to the JSON entry at `path`, recording the current time as its write time.
+pub fn write<T: Serialize>(path: impl AsRef<Path>, value: &T) -> Result<(), CacheError> {
+    let path = path.as_ref();
+    let data = encode(value)?;
+    write_atomic(path, tmp_path(path), &data)?;
+    Ok(())
+}
+
+/// Like [`write`], with `tokio::fs`.
+pub async fn write_async<T: Serialize>(path: impl AsRef<Path>, value: &T) -> Result<(), CacheError> {
+    let path = path.as_ref();
+    let data = encode(value)?;
+    write_atomic_async(path, tmp_path(path), &data).await?;
+    Ok(())
+}
+
+/// The path of the entry in the directory `key`, as written by the attribute macros with their default `format`.
+pub fn entry_path(key: impl AsRef<Path>) -> PathBuf {
+    key.as_ref().join("data.json")
+}
+
+/// The cache root used without `cache_root`: `$XDG_CACHE_HOME/cache_serde`, then `~/.cache/cache_serde`, then
+/// `%LOCALAPPDATA%/cache_serde` on Windows, then `.cache/cache_serde` in the current directory. It is looked up where
+/// the cached function runs, not where it was compiled.
+pub fn default_cache_root() -> String {
+    default_cache_root_with(std::env::var_os)
+}
+
+/// Like [`default_cache_root`], reading the environment variables with `var`.
+fn default_cache_root_with(var: impl Fn(&'static str) -> Option<OsString>) -> String {
+    // relative values of `XDG_CACHE_HOME` are invalid and ignored
+    if let Some(xdg_cache_home) = var("XDG_CACHE_HOME").map(PathBuf::from).filter(|dir| dir.is_absolute()) {
+        return xdg_cache_home.join("cache_serde").to_string_lossy().into_owned();
+    }
+    if let Some(home_dir) = home_dir(&var) {
+        return home_dir.join(".cache").join("cache_serde").to_string_lossy().into_owned();
+    }
+    if let Some(local_app_data) = var("LOCALAPPDATA").filter(|dir| !dir.is_empty()) {
+        return PathBuf::from(local_app_data).join("cache_serde").to_string_lossy().into_owned();
+    }
+    String::from(".cache/cache_serde")
+}
+
+/// Replaces a leading `~` of `path` with the home directory, or with the current directory when it isn't known, so that
+/// a directory literally named `~` is never created. Like [`default_cache_root`], it is resolved where the cached
+/// function runs.
+pub fn expand_home(path: &str) -> String {
+    expand_home_with(path, std::env::var_os)
+}
+
+/// Like [`expand_home`], reading the environment variables with `var`.
+fn expand_home_with(path: &str, var: impl Fn(&'static str) -> Option<OsString>) -> String {
+    match path.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with(['/', '\\']) => {
+            let home_dir = home_dir(&var).unwrap_or_else(|| PathBuf::from("."));
+            format!("{}{}", home_dir.to_string_lossy(), rest)
+        },
+        _ => path.to_string(),
+    }
+}
+
+/// The home directory: `HOME`, or `USERPROFILE` on Windows.
+fn home_dir(var: impl Fn(&'static str) -> Option<OsString>) -> Option<PathBuf> {
+    ["HOME", "USERPROFILE"]
+        .into_iter()
+        .filter_map(var)
+        .find(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+}
+
+/// The magic bytes starting the header written in front of entries with `format_version`, before its version byte.
+pub const HEADER_MAGIC: &[u8; 4] = b"DKCH";
+
+/// Prepends the header of `version` to `data`.
+pub fn with_header(version: u8, data: &[u8]) -> Vec<u8> {
+    let mut headed = Vec::with_capacity(HEADER_MAGIC.len() + 1 + data.len());
+    headed.extend_from_slice(HEADER_MAGIC);
+    headed.push(version);
+    headed.extend_from_slice(data);
+    headed
+}
+
+/// Removes the header of `version` from the front of `data`. An entry without it, or written with another version,
+/// fails with [`CacheError::Deserialize`], so that it is handled like a corrupt one.
+pub fn strip_header(version: u8, mut data: Vec<u8>) -> Result<Vec<u8>, CacheError> {
+    match data.strip_prefix(HEADER_MAGIC.as_slice()).and_then(|rest| rest.first()) {
+        Some(&written) if written == version => {
+            data.drain(..HEADER_MAGIC.len() + 1);
+            Ok(data)
+        },
+        Some(&written) => Err(CacheError::Deserialize(
+            format!("cache entry was written with format version {}, not {}", written, version).into(),
+        )),
+        None => Err(CacheError::Deserialize("cache entry is missing its format header".into())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsString;
+
+    use super::{default_cache_root_with, expand_home_with};
+
+    /// An environment holding only `vars`.
+    fn env<'a>(vars: &'a [(&str, &str)]) -> impl Fn(&'static str) -> Option<OsString> + 'a {
+        move |name| vars.iter().find(|(key, _)| *key == name).map(|(_, value)| OsString::from(value))
+    }
+
+    #[test]
+    fn check_expand_home() {
+        assert_eq!(expand_home_with("~/cache", env(&[("HOME", "/home/user")])), "/home/user/cache");
+        assert_eq!(expand_home_with("~/cache", env(&[("USERPROFILE", "C:\\Users\\user")])), "C:\\Users\\user/cache");
+        assert_eq!(expand_home_with("~", env(&[("HOME", "/home/user")])), "/home/user");
+        assert_eq!(expand_home_with("~user/cache", env(&[("HOME", "/home/user")])), "~user/cache");
+        assert_eq!(expand_home_with("/tmp/~/cache", env(&[("HOME", "/home/user")])), "/tmp/~/cache");
+    }
+
+    #[test]
+    fn check_expand_home_without_home() {
+        for vars in [&[][..], &[("HOME", "")], &[("HOME", ""), ("USERPROFILE", "")]] {
+            assert_eq!(expand_home_with("~/cache", env(vars)), "./cache");
+            assert_eq!(expand_home_with("~", env(vars)), ".");
+        }
+    }
+
+    #[test]
+    fn check_default_cache_root_without_home() {
+        for vars in [&[][..], &[("HOME", "")], &[("HOME", ""), ("XDG_CACHE_HOME", "relative")]] {
+            let root = default_cache_root_with(env(vars));
+            assert_eq!(root, ".cache/cache_serde");
+            assert!(!root.starts_with('~'));
+        }
+        assert_eq!(default_cache_root_with(env(&[("HOME", "/home/user")])), "/home/user/.cache/cache_serde");
+    }
+}
@@ -0,0 +1,18 @@
+//! The runtime side of `disk_cache`: reading, writing and pruning cache entries. The attribute macros of `disk_cache`
+//! emit calls to these functions rather than expanding the same logic into every cached function.
+
+pub use config::CacheConfig;
+pub use error::CacheError;
+pub use event::CacheEvent;
+pub use meta::CacheMeta;
+
+pub mod clock;
+pub mod codec;
+mod config;
+mod error;
+mod event;
+pub mod file;
+pub mod lookup;
+mod meta;
+pub mod prune;
+pub mod shared;
@@ -0,0 +1,158 @@
+//! Looking entries up: whether an entry read is fresh, stale or rejected, across the files it may be read from and the
+//! in-memory tier in front of them.
+
+use std::future::Future;
+use std::num::NonZeroUsize;
+use std::sync::Mutex;
+
+use chrono::{DateTime, Utc};
+use lru::LruCache;
+
+use crate::{clock, CacheError};
+
+/// How the entries of a cached function are judged once read.
+pub struct Policy<'a, T> {
+    /// How long an entry stays fresh, which depends on its value with `cache_errors`.
+    pub expiry: &'a (dyn Fn(&T) -> chrono::Duration + Sync),
+    /// With `validate`, whether a value may be served at all, whatever its age.
+    pub validate: Option<&'a (dyn Fn(&T) -> bool + Sync)>,
+    /// With `refresh_ahead`, how long before it expires a fresh entry is refreshed as well.
+    pub refresh_ahead: Option<chrono::Duration>,
+    /// With `stale_while_revalidate`, whether an expired entry is still served, and refreshed.
+    pub serve_stale: bool,
+}
+
+/// Where a hit was read from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    Memory,
+    Disk,
+    /// From disk, once expired, with `stale_while_revalidate`.
+    Stale,
+}
+
+impl Source {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Source::Memory => "memory",
+            Source::Disk => "disk",
+            Source::Stale => "stale",
+        }
+    }
+}
+
+/// A value read from the cache.
+pub struct Hit<T> {
+    pub value: T,
+    /// When it was written, pushed back by its jitter.
+    pub last_written: DateTime<Utc>,
+    /// How long ago it was written, when it was read.
+    pub age: chrono::Duration,
+    pub source: Source,
+    /// The path it was read from, which is not the cache path with `read_fallback` or `format = "auto"`.
+    pub path: String,
+    /// Whether it is stale or about to expire, so that it should be computed again in the background.
+    pub refresh: bool,
+}
+
+/// What [`Policy::judge`] makes of an entry.
+enum Judgement<T> {
+    Hit(Hit<T>),
+    /// Expired, so that the next place it may be read from is looked at.
+    Expired,
+    /// Rejected by `validate`, which makes the lookup a miss.
+    Rejected,
+}
+
+impl<T> Policy<'_, T> {
+    fn judge(&self, value: T, last_written: DateTime<Utc>, path: String, source: Source) -> Judgement<T> {
+        let age = clock::now().signed_duration_since(last_written);
+        let expiry = (self.expiry)(&value);
+        let fresh = age < expiry;
+        if !fresh && !self.serve_stale {
+            return Judgement::Expired;
+        }
+        if self.validate.is_some_and(|validate| !validate(&value)) {
+            return Judgement::Rejected;
+        }
+        let (source, refresh) = match self.refresh_ahead {
+            _ if !fresh => (Source::Stale, true),
+            Some(refresh_ahead) => (source, expiry - age < refresh_ahead),
+            None => (source, false),
+        };
+        Judgement::Hit(Hit { value, last_written, age, source, path, refresh })
+    }
+}
+
+/// Reads the entry from each of `paths` in turn with `read`, which returns `None` when it is missing, and returns the
+/// first one `policy` accepts. Only the first one read is served once expired.
+pub fn lookup<T>(
+    paths: impl IntoIterator<Item = String>,
+    policy: &Policy<'_, T>,
+    mut read: impl FnMut(String) -> Result<Option<(DateTime<Utc>, T)>, CacheError>,
+) -> Result<Option<Hit<T>>, CacheError> {
+    for path in paths {
+        let Some((last_written, value)) = read(path.clone())? else {
+            continue;
+        };
+        match policy.judge(value, last_written, path, Source::Disk) {
+            Judgement::Hit(hit) => return Ok(Some(hit)),
+            Judgement::Expired => {},
+            Judgement::Rejected => return Ok(None),
+        }
+    }
+    Ok(None)
+}
+
+/// Like [`lookup`], with a `read` returning a future.
+pub async fn lookup_async<T, F, Fut>(paths: impl IntoIterator<Item = String>, policy: &Policy<'_, T>, mut read: F) -> Result<Option<Hit<T>>, CacheError>
+where
+    F: FnMut(String) -> Fut,
+    Fut: Future<Output = Result<Option<(DateTime<Utc>, T)>, CacheError>>,
+{
+    for path in paths {
+        let Some((last_written, value)) = read(path.clone()).await? else {
+            continue;
+        };
+        match policy.judge(value, last_written, path, Source::Disk) {
+            Judgement::Hit(hit) => return Ok(Some(hit)),
+            Judgement::Expired => {},
+            Judgement::Rejected => return Ok(None),
+        }
+    }
+    Ok(None)
+}
+
+/// The in-memory tier of a cached function, with `memory_capacity`: its most recently used values, by cache path.
+pub struct Memory<T>(Mutex<LruCache<String, (DateTime<Utc>, T)>>);
+
+impl<T: Clone> Memory<T> {
+    /// Panics if `capacity` is zero.
+    pub fn new(capacity: usize) -> Self {
+        Memory(Mutex::new(LruCache::new(NonZeroUsize::new(capacity).expect("`memory_capacity` must not be zero"))))
+    }
+
+    /// The value remembered for `cache_path`, provided that it is still fresh. Expired values are left to the disk.
+    pub fn get(&self, cache_path: &str, policy: &Policy<'_, T>) -> Option<Hit<T>> {
+        let (last_written, value) = self.0.lock().unwrap_or_else(|e| e.into_inner()).get(cache_path).cloned()?;
+        let policy = Policy { serve_stale: false, ..*policy };
+        match policy.judge(value, last_written, cache_path.to_string(), Source::Memory) {
+            Judgement::Hit(hit) => Some(hit),
+            Judgement::Expired | Judgement::Rejected => None,
+        }
+    }
+
+    pub fn insert(&self, cache_path: String, last_written: DateTime<Utc>, value: T) {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).put(cache_path, (last_written, value));
+    }
+
+    /// Forgets the value remembered for `cache_path`, with `clear_<name>`.
+    pub fn remove(&self, cache_path: &str) {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).pop(cache_path);
+    }
+
+    /// Forgets every value, with `<name>_clear_all`.
+    pub fn clear(&self) {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).clear();
+    }
+}
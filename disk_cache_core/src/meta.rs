@@ -0,0 +1,15 @@
+//! What is known about a value served by a cached function.
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Returned next to the value by the `<name>_with_meta` function generated for every cached function.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheMeta {
+    /// Whether the value was read from the cache, rather than computed by this call.
+    pub hit: bool,
+    /// How long ago the value was written to the cache, or zero when it was just computed.
+    pub age: Duration,
+    /// Where the entry is stored, as returned by `<name>_cache_path`.
+    pub path: PathBuf,
+}
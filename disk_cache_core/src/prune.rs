@@ -0,0 +1,125 @@
+//! Eviction of cache entries beyond the limits set with `max_entries` and `max_bytes`, and of expired entries.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+use crate::clock;
+
+/// Removes the least recently modified entries next to `entry_dir`, its sibling directories, beyond `max_entries`.
+pub fn entries(entry_dir: impl AsRef<Path>, max_entries: usize) {
+    let Some(Ok(siblings)) = entry_dir.as_ref().parent().map(std::fs::read_dir) else {
+        return;
+    };
+    let mut entries: Vec<(SystemTime, PathBuf)> = siblings
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().map(|file_type| file_type.is_dir()).unwrap_or(false))
+        .map(|entry| {
+            let modified = entry.metadata().and_then(|metadata| metadata.modified());
+            (modified.unwrap_or(SystemTime::UNIX_EPOCH), entry.path())
+        })
+        .collect();
+    if entries.len() > max_entries {
+        entries.sort();
+        let excess = entries.len() - max_entries;
+        for (_, path) in entries.into_iter().take(excess) {
+            let _ = std::fs::remove_dir_all(path);
+        }
+    }
+}
+
+/// Removes the oldest cache files below `budget_root` until their total size fits `max_bytes`. Cache files are those
+/// named `data.*`, or every file with `any_name`, except the temp files of writes in progress and the lock files of
+/// `lock = true`.
+pub fn bytes(budget_root: impl AsRef<Path>, max_bytes: u64, any_name: bool) {
+    let mut files = Vec::new();
+    collect_cache_files(budget_root.as_ref(), any_name, &mut files);
+    let mut total: u64 = files.iter().map(|(_, _, len)| len).sum();
+    files.sort();
+    for (_, path, len) in files {
+        if total <= max_bytes {
+            break;
+        }
+        if std::fs::remove_file(&path).is_ok() {
+            total -= len;
+            // drop the entry directory too, once it is empty
+            if let Some(parent) = path.parent() {
+                let _ = std::fs::remove_dir(parent);
+            }
+        }
+    }
+}
+
+/// The write time of a JSON entry, read from its envelope without its payload.
+#[derive(Deserialize)]
+struct WriteTime {
+    /// Milliseconds since the Unix epoch.
+    written_at: i64,
+    /// Seconds added to the invalidate rate of the entry, with `invalidate_jitter`.
+    #[serde(default)]
+    jitter: i64,
+}
+
+/// Removes the cache files below `root` written at least `ttl` ago, along with the directories they leave empty, and
+/// returns how many were removed. Cache files are those named `data.*`, or every file with `any_name`, except the temp
+/// files of writes in progress and the lock files of `lock = true`. The write time of a JSON entry is read from its
+/// envelope, jitter included, and that of any other entry is its modification time.
+///
+/// Files that disappear or cannot be removed meanwhile are skipped; only failing to read `root` itself is an error,
+/// unless it does not exist.
+pub fn expired(root: impl AsRef<Path>, ttl: Duration, any_name: bool) -> io::Result<usize> {
+    let root = root.as_ref();
+    match std::fs::read_dir(root) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e),
+        Ok(_) => {},
+    }
+    let mut files = Vec::new();
+    collect_cache_files(root, any_name, &mut files);
+    let now = clock::now();
+    let ttl = chrono::Duration::from_std(ttl).unwrap_or(chrono::Duration::MAX);
+    let mut removed = 0;
+    for (modified, path, _) in files {
+        let written_at = embedded_write_time(&path).unwrap_or_else(|| DateTime::<Utc>::from(modified));
+        if now.signed_duration_since(written_at) < ttl {
+            continue;
+        }
+        if std::fs::remove_file(&path).is_ok() {
+            removed += 1;
+            if let Some(parent) = path.parent() {
+                let _ = std::fs::remove_dir(parent);
+            }
+        }
+    }
+    Ok(removed)
+}
+
+/// The write time embedded in the entry at `path`, pushed back by its jitter, if it is a JSON envelope.
+fn embedded_write_time(path: &Path) -> Option<DateTime<Utc>> {
+    if path.extension()? != "json" {
+        return None;
+    }
+    let write_time: WriteTime = serde_json::from_slice(&std::fs::read(path).ok()?).ok()?;
+    Some(DateTime::<Utc>::from_timestamp_millis(write_time.written_at)? + chrono::Duration::seconds(write_time.jitter))
+}
+
+fn collect_cache_files(dir: &Path, any_name: bool, files: &mut Vec<(SystemTime, PathBuf, u64)>) {
+    let Ok(children) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for child in children.filter_map(|child| child.ok()) {
+        let Ok(metadata) = child.metadata() else {
+            continue;
+        };
+        let name = child.file_name().to_string_lossy().into_owned();
+        if metadata.is_dir() {
+            collect_cache_files(&child.path(), any_name, files);
+        } else if (any_name || name.starts_with("data.")) && !name.contains(".tmp.") && !name.ends_with(".lock") {
+            let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            files.push((modified, child.path(), metadata.len()));
+        }
+    }
+}
@@ -0,0 +1,20 @@
+//! State shared by the functions generated for one cached function, such as its in-memory tier.
+//!
+//! The generated functions may be methods, so that they cannot name a common `static` of their own: they meet here
+//! instead, under an id unique to the cached function.
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+
+type Registry = HashMap<(&'static str, TypeId), &'static (dyn Any + Send + Sync)>;
+
+/// The `T` registered under `id`, created with `init` by the first caller. Values live for the rest of the process.
+pub fn get<T: Send + Sync + 'static>(id: &'static str, init: impl FnOnce() -> T) -> &'static T {
+    static REGISTRY: OnceLock<Mutex<Registry>> = OnceLock::new();
+    let mut registry = REGISTRY.get_or_init(Default::default).lock().unwrap_or_else(|e| e.into_inner());
+    let value = *registry
+        .entry((id, TypeId::of::<T>()))
+        .or_insert_with(|| Box::leak(Box::new(init())) as &'static (dyn Any + Send + Sync));
+    value.downcast_ref().expect("registered under the `TypeId` of `T`")
+}
@@ -0,0 +1,55 @@
+use disk_cache_core::codec::{self, Compression, Format, Stamp};
+
+#[test]
+fn check_encode_decode(){
+    for format in [Format::Json, Format::Bincode, Format::Cbor, Format::Ron, Format::MessagePack, Format::Yaml] {
+        let stamp = Stamp::now(Some(30));
+        let data = codec::encode(format, stamp, &vec![1, 2, 3]).unwrap();
+        let (decoded, payload): (Stamp, Vec<i32>) = codec::decode(format, true, data.clone()).unwrap();
+        assert_eq!((decoded, payload), (stamp, vec![1, 2, 3]));
+        assert_eq!(codec::decode_stamp(format, true, data).unwrap(), stamp);
+    }
+    // pretty JSON is read like any other
+    let data = codec::encode(Format::PrettyJson, Stamp::now(None), &"Hello").unwrap();
+    assert!(String::from_utf8(data.clone()).unwrap().contains('\n'));
+    assert_eq!(codec::decode::<String>(Format::Json, false, data).unwrap().1, "Hello");
+}
+
+#[test]
+fn check_decode_as(){
+    let data = codec::encode(Format::Json, Stamp::now(None), &"Hello").unwrap();
+    let (_, converted) = codec::decode_as::<Vec<u8>, String>(Format::Json, false, data).unwrap();
+    assert_eq!(converted, b"Hello");
+    // an entry of neither type does not decode
+    let data = codec::encode(Format::Json, Stamp::now(None), &true).unwrap();
+    assert!(codec::decode_as::<Vec<u8>, String>(Format::Json, false, data).is_err());
+}
+
+#[test]
+fn check_stamp(){
+    let stamp = Stamp { written_at: 1_000, jitter: Some(5) };
+    let data = codec::with_stamp(stamp, b"payload");
+    assert_eq!(codec::split_stamp(true, &data).unwrap(), (stamp, &b"payload"[..]));
+    assert_eq!(stamp.last_written().timestamp_millis(), 6_000);
+    assert!(codec::split_stamp(false, &data[..4]).is_err());
+}
+
+#[test]
+fn check_compression(){
+    let data = b"Hello Hello Hello Hello".to_vec();
+    for compression in [Compression::None, Compression::Gzip(6), Compression::Zstd(3)] {
+        let compressed = compression.compress(None, data.clone()).unwrap();
+        assert_eq!(compression.decompress(false, compressed).unwrap(), data);
+        // below `min_bytes`, the flag byte says the data is left as it is
+        let flagged = compression.compress(Some(1024), data.clone()).unwrap();
+        assert_eq!(flagged[0], 0);
+        assert_eq!(compression.decompress(true, flagged).unwrap(), data);
+    }
+}
+
+#[test]
+fn check_of_path(){
+    assert_eq!(Format::of_path("./cache/f/1/data.bin.gz").unwrap(), Format::Bincode);
+    assert_eq!(Format::of_path("./cache/f.d/1/data.yaml").unwrap(), Format::Yaml);
+    assert!(Format::of_path("./cache/f/1/data").is_err());
+}
@@ -0,0 +1,132 @@
+use std::time::Duration;
+
+use disk_cache_core::file;
+
+#[test]
+fn check_sanitize_key(){
+    assert_eq!(file::sanitize_key("../escaped".to_string()), "..%2Fescaped");
+    assert_eq!(file::sanitize_key("..".to_string()), "%2E%2E");
+    assert_eq!(file::sanitize_key("100%".to_string()), "100%25");
+    // characters and names Windows rejects are encoded on every platform
+    assert_eq!(file::sanitize_key("C:\\what?*".to_string()), "C%3A%5Cwhat%3F%2A");
+    assert_eq!(file::sanitize_key("<a|b>\"\n".to_string()), "%3Ca%7Cb%3E%22%0A");
+    assert_eq!(file::sanitize_key("trailing.".to_string()), "trailing%2E");
+    assert_eq!(file::sanitize_key("trailing ".to_string()), "trailing%20");
+    assert_eq!(file::sanitize_key("con".to_string()), "%63on");
+    assert_eq!(file::sanitize_key("LPT1.txt".to_string()), "%4CPT1.txt");
+    assert_eq!(file::sanitize_key("console".to_string()), "console");
+    // a value too long for a file name is cut short and followed by a hash of the whole value
+    let long = file::sanitize_key("a".repeat(300));
+    assert_eq!(long.len(), 255);
+    assert!(long.starts_with(&"a".repeat(237)));
+    assert!(long[237..].starts_with("%~"));
+    assert_ne!(long, file::sanitize_key(format!("{}b", "a".repeat(299))));
+    let escaped = file::sanitize_key(":".repeat(100));
+    assert!(escaped.len() <= 255);
+    assert!(escaped.split("%~").next().unwrap().split('%').skip(1).all(|escape| escape == "3A"));
+    assert_eq!(file::sanitize_key("é".repeat(200)).len(), 254);
+}
+
+#[test]
+fn check_read_if_valid(){
+    // clear the cache
+    let cache_path = "./cache/core/read/data.json";
+    std::fs::remove_dir_all("./cache/core/read").unwrap_or_default();
+    assert_eq!(file::read_if_valid::<String>(cache_path, Duration::from_secs(3600)).unwrap(), None);
+    file::write(cache_path, &"Hello".to_string()).unwrap();
+    assert_eq!(file::read_if_valid::<String>(cache_path, Duration::from_secs(3600)).unwrap().as_deref(), Some("Hello"));
+    // an expired entry is a miss, but stays in place
+    assert_eq!(file::read_if_valid::<String>(cache_path, Duration::ZERO).unwrap(), None);
+    assert!(std::fs::metadata(cache_path).is_ok());
+    // a corrupt one is removed
+    std::fs::write(cache_path, "not json").unwrap();
+    assert_eq!(file::read_if_valid::<String>(cache_path, Duration::from_secs(3600)).unwrap(), None);
+    assert!(std::fs::metadata(cache_path).is_err());
+}
+
+#[tokio::test]
+async fn check_write_async(){
+    // clear the cache
+    let cache_path = "./cache/core/write_async/data.json";
+    std::fs::remove_dir_all("./cache/core/write_async").unwrap_or_default();
+    file::write_async(cache_path, &vec![1, 2, 3]).await.unwrap();
+    let cached: Option<Vec<i32>> = file::read_if_valid_async(cache_path, Duration::from_secs(3600)).await.unwrap();
+    assert_eq!(cached, Some(vec![1, 2, 3]));
+    // no temp file is left behind
+    assert_eq!(std::fs::read_dir("./cache/core/write_async").unwrap().count(), 1);
+}
+
+#[test]
+fn check_prune_entries(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/core/prune").unwrap_or_default();
+    for entry in 0..4 {
+        file::write(format!("./cache/core/prune/{entry}/data.json"), &entry).unwrap();
+        // keep the modification times apart
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    disk_cache_core::prune::entries("./cache/core/prune/3", 2);
+    let mut remaining: Vec<String> = std::fs::read_dir("./cache/core/prune").unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    remaining.sort();
+    assert_eq!(remaining, vec!["2", "3"]);
+}
+
+#[test]
+fn check_header(){
+    let data = file::with_header(3, b"payload");
+    assert_eq!(&data[..5], b"DKCH\x03");
+    assert_eq!(file::strip_header(3, data.clone()).unwrap(), b"payload");
+    // another version, or no header at all, is corrupt
+    assert!(matches!(file::strip_header(4, data), Err(disk_cache_core::CacheError::Deserialize(_))));
+    assert!(matches!(file::strip_header(3, b"payload".to_vec()), Err(disk_cache_core::CacheError::Deserialize(_))));
+}
+
+#[test]
+fn check_prune_expired(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/core/expired").unwrap_or_default();
+    file::write("./cache/core/expired/fresh/data.json", &1).unwrap();
+    let old = format!(r#"{{"written_at":{},"payload":2}}"#, chrono::Utc::now().timestamp_millis() - 2 * 3600 * 1000);
+    std::fs::create_dir_all("./cache/core/expired/old").unwrap();
+    std::fs::write("./cache/core/expired/old/data.json", old).unwrap();
+    // the jitter of an entry extends its life
+    let jittered = format!(r#"{{"written_at":{},"jitter":7200,"payload":3}}"#, chrono::Utc::now().timestamp_millis() - 2 * 3600 * 1000);
+    std::fs::create_dir_all("./cache/core/expired/jittered").unwrap();
+    std::fs::write("./cache/core/expired/jittered/data.json", jittered).unwrap();
+    assert_eq!(disk_cache_core::prune::expired("./cache/core/expired", Duration::from_secs(3600), false).unwrap(), 1);
+    assert!(!std::path::Path::new("./cache/core/expired/old").exists());
+    assert!(std::path::Path::new("./cache/core/expired/fresh/data.json").exists());
+    assert!(std::path::Path::new("./cache/core/expired/jittered/data.json").exists());
+    // entries without an envelope expire by their modification time
+    std::fs::write("./cache/core/expired/fresh/data.bin", b"raw").unwrap();
+    assert_eq!(disk_cache_core::prune::expired("./cache/core/expired", Duration::ZERO, false).unwrap(), 3);
+    assert_eq!(disk_cache_core::prune::expired("./cache/core/missing", Duration::ZERO, false).unwrap(), 0);
+}
+
+#[test]
+fn check_prune_expired_any_name(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/core/expired_any_name").unwrap_or_default();
+    // entries written with `file_name`, next to the temp file of a write in progress and a lock file
+    file::write("./cache/core/expired_any_name/1.json", &1).unwrap();
+    file::write("./cache/core/expired_any_name/nested/2.bin", &2).unwrap();
+    std::fs::write("./cache/core/expired_any_name/3.json.tmp.0", b"partial").unwrap();
+    std::fs::write("./cache/core/expired_any_name/3.json.lock", b"").unwrap();
+    // only files named `data.*` are entries by default
+    assert_eq!(disk_cache_core::prune::expired("./cache/core/expired_any_name", Duration::ZERO, false).unwrap(), 0);
+    assert_eq!(disk_cache_core::prune::expired("./cache/core/expired_any_name", Duration::ZERO, true).unwrap(), 2);
+    assert!(!std::path::Path::new("./cache/core/expired_any_name/nested").exists());
+    assert!(std::path::Path::new("./cache/core/expired_any_name/3.json.tmp.0").exists());
+    assert!(std::path::Path::new("./cache/core/expired_any_name/3.json.lock").exists());
+}
+
+#[test]
+fn check_expand_home(){
+    // only a leading `~` on its own stands for the home directory
+    assert!(!file::expand_home("~/cache").starts_with('~'));
+    assert!(file::expand_home("~/cache").ends_with("/cache"));
+    assert_eq!(file::expand_home("~user/cache"), "~user/cache");
+    assert_eq!(file::expand_home("./cache"), "./cache");
+}
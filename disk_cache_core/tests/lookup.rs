@@ -0,0 +1,60 @@
+use chrono::{DateTime, Duration, Utc};
+use disk_cache_core::lookup::{self, Memory, Policy, Source};
+use disk_cache_core::{clock, CacheError};
+
+/// Reads the entries of `entries` by path, each written `age` seconds ago.
+fn read<'a>(entries: &'a [(&'a str, i64, i32)]) -> impl FnMut(String) -> Result<Option<(DateTime<Utc>, i32)>, CacheError> + 'a {
+    move |path| {
+        Ok(entries
+            .iter()
+            .find(|(entry_path, _, _)| *entry_path == path)
+            .map(|(_, age, value)| (clock::now() - Duration::seconds(*age), *value)))
+    }
+}
+
+fn paths() -> Vec<String> {
+    vec!["a".to_string(), "b".to_string()]
+}
+
+#[test]
+fn check_lookup(){
+    let policy = Policy { expiry: &|_: &i32| Duration::seconds(60), validate: None, refresh_ahead: None, serve_stale: false };
+    // an expired entry makes way for the next path
+    let hit = lookup::lookup(paths(), &policy, read(&[("a", 120, 1), ("b", 10, 2)])).unwrap().unwrap();
+    assert_eq!((hit.value, hit.path.as_str(), hit.source, hit.refresh), (2, "b", Source::Disk, false));
+    assert!(lookup::lookup(paths(), &policy, read(&[("a", 120, 1)])).unwrap().is_none());
+    // a rejected value is a miss, whatever comes after it
+    let policy = Policy { validate: Some(&|value: &i32| *value > 1), ..policy };
+    assert!(lookup::lookup(paths(), &policy, read(&[("a", 10, 1), ("b", 10, 2)])).unwrap().is_none());
+}
+
+#[test]
+fn check_lookup_refresh(){
+    let policy = Policy { expiry: &|_: &i32| Duration::seconds(60), validate: None, refresh_ahead: Some(Duration::seconds(20)), serve_stale: true };
+    let hit = lookup::lookup(paths(), &policy, read(&[("a", 10, 1)])).unwrap().unwrap();
+    assert!(!hit.refresh);
+    let hit = lookup::lookup(paths(), &policy, read(&[("a", 50, 1)])).unwrap().unwrap();
+    assert!(hit.refresh);
+    // an expired entry is served stale from the first path read
+    let hit = lookup::lookup(paths(), &policy, read(&[("a", 120, 1), ("b", 10, 2)])).unwrap().unwrap();
+    assert_eq!((hit.value, hit.source, hit.refresh), (1, Source::Stale, true));
+}
+
+#[test]
+fn check_memory(){
+    let memory = Memory::new(1);
+    let policy = Policy { expiry: &|_: &i32| Duration::seconds(60), validate: None, refresh_ahead: None, serve_stale: true };
+    memory.insert("a".to_string(), clock::now(), 1);
+    assert_eq!(memory.get("a", &policy).map(|hit| (hit.value, hit.source)), Some((1, Source::Memory)));
+    // the least recently used value makes way, and expired ones are never served
+    memory.insert("b".to_string(), clock::now() - Duration::seconds(120), 2);
+    assert!(memory.get("a", &policy).is_none());
+    assert!(memory.get("b", &policy).is_none());
+    // a removed value is forgotten
+    memory.insert("c".to_string(), clock::now(), 3);
+    memory.remove("c");
+    assert!(memory.get("c", &policy).is_none());
+    memory.insert("d".to_string(), clock::now(), 4);
+    memory.clear();
+    assert!(memory.get("d", &policy).is_none());
+}
@@ -0,0 +1,3003 @@
+use proc_macro::TokenStream;
+use quote::{format_ident, quote, quote_spanned};
+use syn::spanned::Spanned;
+use syn::ReturnType;
+use syn::parse::{Parse, ParseStream};
+use syn::{parse_macro_input, ItemFn, Lit, Meta, NestedMeta, Type};
+
+/// `cache_async` is a procedural macro that caches the results of asynchronous functions to a specified directory.
+/// 
+/// Return type of the function must implement both `Serialize` and `Deserialize` from the `serde` crate in order to 
+/// be cached and retrieved correctly, or, if the return type is a `Result<T, E>`, then `T` must implement `Serialize`.
+/// 
+/// If a cache file exists and is still valid, based on the provided `invalidate_rate`, returns cached result. 
+/// Otherwise, the function is executed, and the result is saved to the cache for future use. Unless `on_error` is
+/// `"panic"` or `"fallback"`, the return type is wrapped in a `Result<T, disk_cache::CacheError>`. The README describes
+/// every attribute and feature in detail.
+///
+/// # Arguments
+/// - `cache_root`: The directory of the entries, with `{arg}` placeholders. `{{` and `}}` are literal braces.
+/// - `cache_root_env`: An environment variable replacing the fixed directories of `cache_root` at runtime.
+/// - `config`: A `disk_cache::CacheConfig` setting the root and invalidate rate of the function.
+/// - `read_fallback`: Another root holding a read-only copy of the cache, read when an entry is missing or expired.
+/// - `namespace` and `cache_id`: Path segments inserted after the fixed directories of `cache_root`.
+/// - `file_name`: The name of the cache file, in place of `data.<extension>`.
+/// - `flat`: When `true`, the function's name is not inserted into the cache path.
+/// - `key_includes_body`: When `true`, a hash of the function's body is inserted into the cache path.
+/// - `invalidate_rate`: How long an entry stays valid, in seconds, with a unit suffix, or `"never"`. 3600 seconds by
+///   default. An argument marked `#[ttl]` overrides it for each call.
+/// - `invalidate_jitter`: Up to how many seconds are randomly added to the invalidate rate of each entry.
+/// - `format`: `"json"` (the default), `"bincode"`, `"cbor"`, `"ron"`, `"messagepack"`, `"toml"`, `"yaml"` or `"auto"`.
+/// - `serialize_with` and `deserialize_with`: A pair of functions encoding the value in place of serde.
+/// - `pretty`: When `true`, JSON entries are written indented.
+/// - `deterministic`: When `true`, a cached `HashMap` or `HashSet` is written in the order of its keys.
+/// - `concrete`: The type cached behind a `Box<dyn Trait>` return type.
+/// - `owned_as`: The owned form cached for a reference return type.
+/// - `read_as`: An older cached type, converted with `From`, that entries may still be written in.
+/// - `collect`: When `true`, an `impl Stream<Item = T>` is cached by collecting its items.
+/// - `compress`, `compress_level` and `compress_min_bytes`: Gzip or zstd compression of the entries.
+/// - `format_version`: A version written in a header in front of every entry; other versions are misses.
+/// - `integrity`: A `"crc32"` or `"blake3"` checksum checked whenever an entry is read.
+/// - `key` and `hash`: Whether the entry is keyed by `cache_root` alone or by a hash of every argument.
+/// - `write_mode`: `"spawn"` (the default) writes the entry in the background, `"blocking"` before returning.
+/// - `spawn`: How background tasks run: `"detached"` (the default), `"local"` or `"inline"`.
+/// - `read_only`: When `true`, entries are read but never written nor removed.
+/// - `single_flight`: When `true`, concurrent calls for the same entry in the process run the function once.
+/// - `lock`: When `true`, concurrent calls for the same entry across processes run the function once.
+/// - `on_error`: `"propagate"` (the default), `"panic"` or `"fallback"`, for errors of the cache layer.
+/// - `cache_errors` and `error_invalidate_rate`: Whether `Err` values are cached too, and for how long.
+/// - `timestamp`: `"embedded"` (the default) stores the write time in the entry, `"mtime"` uses the file's.
+/// - `bypass_env`: An environment variable skipping the cache when set. `DISK_CACHE_BYPASS` by default.
+/// - `memory_capacity`: How many entries are also kept in memory, in front of the disk.
+/// - `stale_while_revalidate`: When `true`, expired entries are served while they are refreshed in the background.
+/// - `refresh_ahead`: How long before they expire valid entries are refreshed in the background.
+/// - `max_entries` and `max_bytes`: Limits on the entries kept, the least recently modified being removed first.
+/// - `cache_if` and `min_compute_time_ms`: Conditions a computed value must meet to be cached.
+/// - `validate`: A function rejecting values read from the cache.
+/// - `touch_on_hit`: When `true`, a hit updates the modification time `max_entries` and `max_bytes` evict by.
+/// - `on_write_error` and `on_event`: Functions called when a write fails, and on every hit, miss and write.
+/// - `backend`: `"files"` (the default), `"sled"`, `"redis"` or `"files+redis"`, the last two with `redis_url`.
+/// - `encrypt` and `encrypt_key_env`: Entries encrypted with the key in the given environment variable.
+/// - `deny_unit`: When `true`, a function returning `()` is a compile error rather than a warning.
+/// - `is_result`: Whether the return type is a `Result`, for type aliases the macro cannot see through.
+///
+/// # Generated Functions
+/// Alongside the function, the macro generates `clear_<name>`, `<name>_is_cached`, `<name>_prime`,
+/// `<name>_with_meta`, `<name>_cache_path` and `<name>_clear_all`.
+#[proc_macro_attribute]
+pub fn cache_async(args: TokenStream, item: TokenStream) -> TokenStream {
+    let io = if cfg!(feature = "async-std") { Io::AsyncStd } else { Io::Tokio };
+    expand(args, item, io, OnError::Propagate)
+}
+
+/// `cache` is the synchronous sibling of [`cache_async`], for plain blocking functions.
+///
+/// It accepts the same attributes as `cache_async`, but the generated code uses `std::fs` instead of `tokio::fs`,
+/// so it can be used in projects without an async runtime. Unless `on_error` is `"panic"` or `"fallback"`, the
+/// decorated function's return type will be wrapped in a `Result<T, disk_cache::CacheError>`.
+#[proc_macro_attribute]
+pub fn cache(args: TokenStream, item: TokenStream) -> TokenStream {
+    expand(args, item, Io::Std, OnError::Propagate)
+}
+
+/// `cache_fallible` caches functions returning a `Result<T, E>` without changing their signature.
+///
+/// It accepts the same attributes as [`cache_async`] except `on_error`, and caches `async fn`s like `cache_async` and
+/// other functions like [`cache`]. Instead of wrapping the return type in another `Result`, errors of the cache layer
+/// are converted into `E`, through `std::io::Error`, so `E` must implement `From<std::io::Error>`. The generated
+/// `clear_<name>`, `<name>_is_cached`, `<name>_prime` and `<name>_with_meta` functions still return a
+/// `disk_cache::CacheError`.
+#[proc_macro_attribute]
+pub fn cache_fallible(args: TokenStream, item: TokenStream) -> TokenStream {
+    let io = match syn::parse::<ItemFn>(item.clone()) {
+        Ok(input) if input.sig.asyncness.is_none() => Io::Std,
+        _ if cfg!(feature = "async-std") => Io::AsyncStd,
+        _ => Io::Tokio,
+    };
+    expand(args, item, io, OnError::Convert)
+}
+
+fn expand(args: TokenStream, item: TokenStream, io: Io, mut on_error: OnError) -> TokenStream {
+    // Parse the input function
+    let mut input = parse_macro_input!(item as ItemFn);
+    let CacheArgs { literals: args, paths } = parse_macro_input!(args as CacheArgs);
+    // an argument marked `#[ttl]` overrides `invalidate_rate` for each call; the marker is not kept in the output
+    let mut ttl_arg = None;
+    for arg in input.sig.inputs.iter_mut() {
+        let syn::FnArg::Typed(pat_type) = arg else {
+            continue;
+        };
+        let Some(position) = pat_type.attrs.iter().position(|attr| attr.path.is_ident("ttl")) else {
+            continue;
+        };
+        let marker = pat_type.attrs.remove(position);
+        let syn::Pat::Ident(pat_ident) = &*pat_type.pat else {
+            return syn::Error::new_spanned(&pat_type.pat, "a `#[ttl]` argument must be a plain identifier")
+                .to_compile_error()
+                .into();
+        };
+        if ttl_arg.is_some() {
+            return syn::Error::new_spanned(marker, "only one argument can be marked `#[ttl]`")
+                .to_compile_error()
+                .into();
+        }
+        let ident = &pat_ident.ident;
+        // a `Duration` counts whole seconds, any integer is a number of seconds, and either may be behind references
+        let mut ty = &*pat_type.ty;
+        let mut derefs = Vec::new();
+        while let Type::Reference(reference) = ty {
+            ty = &reference.elem;
+            derefs.push(quote! { * });
+        }
+        let is_duration = matches!(ty, Type::Path(type_path) if type_path.path.segments.last().is_some_and(|segment| segment.ident == "Duration"));
+        ttl_arg = Some((ident.clone(), if is_duration {
+            quote! { (#ident.as_secs() as i64) }
+        } else {
+            quote! { (#(#derefs)* #ident as i64) }
+        }));
+    }
+
+    if let (Io::Std, Some(asyncness)) = (&io, &input.sig.asyncness) {
+        return syn::Error::new_spanned(asyncness, "`cache` does not support async functions, use `cache_async` instead")
+            .to_compile_error()
+            .into();
+    }
+
+    let func_name = &input.sig.ident;
+    let func_body = &input.block;
+    let func_args = &input.sig.inputs;
+    // a plain `fn` returning `impl Future<Output = T>` is cached like an `async fn` returning `T`
+    let future_output = match (&io, &input.sig.asyncness) {
+        (Io::Tokio | Io::AsyncStd, None) => future_output_type(&input.sig.output),
+        _ => None,
+    };
+    let func_output = &match (&input.sig.output, future_output) {
+        (ReturnType::Type(arrow, _), Some(output)) => ReturnType::Type(*arrow, Box::new(output.clone())),
+        (output, _) => output.clone(),
+    };
+
+    // attributes
+    // without `cache_root`, the default root is looked up at runtime, and only stands in for the fixed directories here
+    let mut cache_path = String::from(".cache/cache_serde");
+    let mut cache_root_span = proc_macro2::Span::call_site();
+    let mut has_cache_root = false;
+    let mut config = None;
+    let mut invalidate_rate = None;
+    let mut never_expires = None;
+    let mut invalidate_jitter = None;
+    let mut format = Format::Json;
+    let mut format_lit = None;
+    let mut serialize_with = None;
+    let mut deserialize_with = None;
+    let mut compression = Compression::None;
+    let mut compress_level = None;
+    let mut compress_min_bytes = None;
+    let mut key_args = false;
+    let mut blocking_write = false;
+    let mut spawn = Spawn::Detached;
+    let mut single_flight = false;
+    let mut lock = None;
+    let mut read_only = None;
+    let mut cache_errors = None;
+    let mut error_invalidate_rate = 60;
+    let mut embedded_timestamp = true;
+    let mut bypass_env = String::from("DISK_CACHE_BYPASS");
+    let mut memory_capacity = None;
+    let mut min_compute_time_ms = None;
+    let mut format_version = None;
+    let mut integrity = None;
+    let mut hash = None;
+    let mut cache_root_env = None;
+    let mut read_fallback = None;
+    let mut stale_while_revalidate = false;
+    let mut refresh_ahead = None;
+    let mut max_entries = None;
+    let mut max_bytes = None;
+    let mut touch_on_hit = None;
+    let mut deny_unit = false;
+    let mut is_result_override = None;
+    let mut on_write_error = None;
+    let mut on_event = None;
+    let mut cache_if = None;
+    let mut concrete = None;
+    let mut collect = None;
+    let mut validate = None;
+    let mut owned_as = None;
+    let mut read_as = None;
+    let mut namespace = None;
+    let mut cache_id = None;
+    let mut flat = false;
+    let mut key_includes_body = false;
+    let mut deterministic = None;
+    let mut pretty = None;
+    let mut file_name_lit = None;
+    let mut backend = Backend::Files;
+    let mut redis_url = None;
+    let mut encrypt = false;
+    let mut encrypt_key_env = String::from("DISK_CACHE_KEY");
+    // Parse the attributes
+    for arg in args.iter() {
+        match arg {
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("cache_root") => {
+                let Lit::Str(lit_str) = &nv.lit else {
+                    return expected_literal(nv, "a string");
+                };
+                cache_path = lit_str.value();
+                cache_root_span = lit_str.span();
+                has_cache_root = true;
+            },
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("backend") => {
+                let Lit::Str(lit_str) = &nv.lit else {
+                    return expected_literal(nv, "a string");
+                };
+                backend = match lit_str.value().as_str() {
+                    "files" => Backend::Files,
+                    "sled" if cfg!(feature = "sled") => Backend::Sled(lit_str),
+                    "sled" => {
+                        return syn::Error::new_spanned(lit_str, "`backend = \"sled\"` requires the `sled` feature of `disk_cache`")
+                            .to_compile_error()
+                            .into();
+                    }
+                    "redis" if cfg!(feature = "redis") => Backend::Redis(lit_str),
+                    "redis" => {
+                        return syn::Error::new_spanned(lit_str, "`backend = \"redis\"` requires the `redis` feature of `disk_cache`")
+                            .to_compile_error()
+                            .into();
+                    }
+                    "files+redis" if cfg!(feature = "redis") => Backend::Tiered(lit_str),
+                    "files+redis" => {
+                        return syn::Error::new_spanned(lit_str, "`backend = \"files+redis\"` requires the `redis` feature of `disk_cache`")
+                            .to_compile_error()
+                            .into();
+                    }
+                    other => {
+                        let message = format!("unknown cache backend `{}`, expected one of `files`, `sled`, `redis`, `files+redis`", other);
+                        return syn::Error::new_spanned(lit_str, message).to_compile_error().into();
+                    }
+                };
+            },
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("redis_url") => {
+                let Lit::Str(lit_str) = &nv.lit else {
+                    return expected_literal(nv, "a string");
+                };
+                redis_url = Some(lit_str.value());
+            },
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("encrypt") => {
+                let Lit::Bool(lit_bool) = &nv.lit else {
+                    return expected_literal(nv, "a boolean");
+                };
+                if lit_bool.value && !cfg!(feature = "encrypt") {
+                    return syn::Error::new_spanned(lit_bool, "`encrypt = true` requires the `encrypt` feature of `disk_cache`")
+                        .to_compile_error()
+                        .into();
+                }
+                encrypt = lit_bool.value;
+            },
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("collect") => {
+                let Lit::Bool(lit_bool) = &nv.lit else {
+                    return expected_literal(nv, "a boolean");
+                };
+                if lit_bool.value && !cfg!(feature = "stream") {
+                    return syn::Error::new_spanned(lit_bool, "`collect = true` requires the `stream` feature of `disk_cache`")
+                        .to_compile_error()
+                        .into();
+                }
+                collect = lit_bool.value.then_some(lit_bool);
+            },
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("encrypt_key_env") => {
+                let Lit::Str(lit_str) = &nv.lit else {
+                    return expected_literal(nv, "a string");
+                };
+                encrypt_key_env = lit_str.value();
+            },
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("namespace") || nv.path.is_ident("cache_id") => {
+                let Lit::Str(lit_str) = &nv.lit else {
+                    return expected_literal(nv, "a string");
+                };
+                let value = lit_str.value();
+                if value.is_empty() || value == "." || value == ".." || value.contains(['/', '\\', '{', '}']) {
+                    let name = nv.path.get_ident().map(|ident| ident.to_string()).unwrap_or_default();
+                    return syn::Error::new_spanned(lit_str, format!("`{}` must be a single path segment without braces", name))
+                        .to_compile_error()
+                        .into();
+                }
+                if nv.path.is_ident("namespace") {
+                    namespace = Some(value);
+                } else {
+                    cache_id = Some(value);
+                }
+            },
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("cache_root_env") => {
+                let Lit::Str(lit_str) = &nv.lit else {
+                    return expected_literal(nv, "a string");
+                };
+                cache_root_env = Some(lit_str.value());
+            },
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("read_fallback") => {
+                let Lit::Str(lit_str) = &nv.lit else {
+                    return expected_literal(nv, "a string");
+                };
+                read_fallback = Some(lit_str);
+            },
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("invalidate_rate") => {
+                // entries that never expire are served without comparing their age to anything
+                match &nv.lit {
+                    Lit::Str(lit_str) if lit_str.value() == "never" => never_expires = Some(lit_str),
+                    lit => match parse_seconds(lit) {
+                        Ok(seconds) => invalidate_rate = Some(seconds),
+                        Err(e) => return e.to_compile_error().into(),
+                    },
+                }
+            },
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("invalidate_jitter") => {
+                match parse_seconds(&nv.lit) {
+                    Ok(seconds) => invalidate_jitter = Some((seconds, &nv.lit)),
+                    Err(e) => return e.to_compile_error().into(),
+                }
+            },
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("format") => {
+                let Lit::Str(lit_str) = &nv.lit else {
+                    return expected_literal(nv, "a string");
+                };
+                format = match lit_str.value().as_str() {
+                    "json" => Format::Json,
+                    "bincode" => Format::Bincode,
+                    "cbor" => Format::Cbor,
+                    "ron" => Format::Ron,
+                    "messagepack" => Format::MessagePack,
+                    "toml" => Format::Toml,
+                    "yaml" => Format::Yaml,
+                    "auto" => Format::Auto,
+                    other => {
+                        let message = format!("unknown cache format `{}`, expected one of `json`, `bincode`, `cbor`, `ron`, `messagepack`, `toml`, `yaml`, `auto`", other);
+                        return syn::Error::new_spanned(lit_str, message).to_compile_error().into();
+                    }
+                };
+                format_lit = Some(lit_str);
+            },
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("serialize_with") || nv.path.is_ident("deserialize_with") => {
+                let Lit::Str(lit_str) = &nv.lit else {
+                    return expected_literal(nv, "a path or string");
+                };
+                let path = match lit_str.parse::<syn::Path>() {
+                    Ok(path) => path,
+                    Err(e) => return e.to_compile_error().into(),
+                };
+                if nv.path.is_ident("serialize_with") {
+                    serialize_with = Some(path);
+                } else {
+                    deserialize_with = Some(path);
+                }
+            },
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("compress") => {
+                let Lit::Str(lit_str) = &nv.lit else {
+                    return expected_literal(nv, "a string");
+                };
+                compression = match lit_str.value().as_str() {
+                    "none" => Compression::None,
+                    "gzip" => Compression::Gzip(6),
+                    "zstd" => Compression::Zstd(3),
+                    other => {
+                        let message = format!("unknown compression `{}`, expected one of `none`, `gzip`, `zstd`", other);
+                        return syn::Error::new_spanned(lit_str, message).to_compile_error().into();
+                    }
+                };
+            },
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("compress_level") => {
+                let Lit::Int(lit_int) = &nv.lit else {
+                    return expected_literal(nv, "an integer");
+                };
+                compress_level = Some(lit_int);
+            },
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("compress_min_bytes") => {
+                let Lit::Int(lit_int) = &nv.lit else {
+                    return expected_literal(nv, "an integer");
+                };
+                if let Err(e) = lit_int.base10_parse::<usize>() {
+                    return e.to_compile_error().into();
+                }
+                compress_min_bytes = Some(lit_int);
+            },
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("key") => {
+                let Lit::Str(lit_str) = &nv.lit else {
+                    return expected_literal(nv, "a string");
+                };
+                key_args = match lit_str.value().as_str() {
+                    "path" => false,
+                    "args" => true,
+                    other => {
+                        let message = format!("unknown cache key `{}`, expected one of `path`, `args`", other);
+                        return syn::Error::new_spanned(lit_str, message).to_compile_error().into();
+                    }
+                };
+            },
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("write_mode") => {
+                let Lit::Str(lit_str) = &nv.lit else {
+                    return expected_literal(nv, "a string");
+                };
+                blocking_write = match lit_str.value().as_str() {
+                    "spawn" => false,
+                    "blocking" => true,
+                    other => {
+                        let message = format!("unknown write mode `{}`, expected one of `spawn`, `blocking`", other);
+                        return syn::Error::new_spanned(lit_str, message).to_compile_error().into();
+                    }
+                };
+            },
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("spawn") => {
+                let Lit::Str(lit_str) = &nv.lit else {
+                    return expected_literal(nv, "a string");
+                };
+                spawn = match lit_str.value().as_str() {
+                    "detached" => Spawn::Detached,
+                    "local" if matches!(io, Io::Tokio) => Spawn::Local,
+                    "local" => {
+                        return syn::Error::new_spanned(lit_str, "`spawn = \"local\"` requires `cache_async` with the tokio runtime")
+                            .to_compile_error()
+                            .into();
+                    }
+                    "inline" => Spawn::Inline,
+                    other => {
+                        let message = format!("unknown spawn mode `{}`, expected one of `detached`, `local`, `inline`", other);
+                        return syn::Error::new_spanned(lit_str, message).to_compile_error().into();
+                    }
+                };
+            },
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("single_flight") => {
+                let Lit::Bool(lit_bool) = &nv.lit else {
+                    return expected_literal(nv, "a boolean");
+                };
+                single_flight = lit_bool.value;
+            },
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("lock") => {
+                let Lit::Bool(lit_bool) = &nv.lit else {
+                    return expected_literal(nv, "a boolean");
+                };
+                if lit_bool.value && !cfg!(feature = "lock") {
+                    return syn::Error::new_spanned(lit_bool, "`lock = true` requires the `lock` feature of `disk_cache`")
+                        .to_compile_error()
+                        .into();
+                }
+                lock = lit_bool.value.then_some(lit_bool);
+            },
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("file_name") => {
+                let Lit::Str(lit_str) = &nv.lit else {
+                    return expected_literal(nv, "a string");
+                };
+                let value = lit_str.value();
+                if value.is_empty() || value == "." || value == ".." || value.contains(['/', '\\']) {
+                    return syn::Error::new_spanned(lit_str, "`file_name` must be a single path segment")
+                        .to_compile_error()
+                        .into();
+                }
+                file_name_lit = Some(lit_str);
+            },
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("flat") => {
+                let Lit::Bool(lit_bool) = &nv.lit else {
+                    return expected_literal(nv, "a boolean");
+                };
+                flat = lit_bool.value;
+            },
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("key_includes_body") => {
+                let Lit::Bool(lit_bool) = &nv.lit else {
+                    return expected_literal(nv, "a boolean");
+                };
+                key_includes_body = lit_bool.value;
+            },
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("deterministic") => {
+                let Lit::Bool(lit_bool) = &nv.lit else {
+                    return expected_literal(nv, "a boolean");
+                };
+                deterministic = lit_bool.value.then_some(lit_bool);
+            },
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("pretty") => {
+                let Lit::Bool(lit_bool) = &nv.lit else {
+                    return expected_literal(nv, "a boolean");
+                };
+                pretty = lit_bool.value.then_some(lit_bool);
+            },
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("read_only") => {
+                let Lit::Bool(lit_bool) = &nv.lit else {
+                    return expected_literal(nv, "a boolean");
+                };
+                read_only = lit_bool.value.then_some(lit_bool);
+            },
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("on_write_error") || nv.path.is_ident("on_event") || nv.path.is_ident("cache_if") => {
+                let Lit::Str(lit_str) = &nv.lit else {
+                    return expected_literal(nv, "a path or string");
+                };
+                let path = match lit_str.parse::<syn::Path>() {
+                    Ok(path) => path,
+                    Err(e) => return e.to_compile_error().into(),
+                };
+                if nv.path.is_ident("on_write_error") {
+                    on_write_error = Some(path);
+                } else if nv.path.is_ident("on_event") {
+                    on_event = Some(path);
+                } else {
+                    cache_if = Some(path);
+                }
+            },
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("deny_unit") => {
+                let Lit::Bool(lit_bool) = &nv.lit else {
+                    return expected_literal(nv, "a boolean");
+                };
+                deny_unit = lit_bool.value;
+            },
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("is_result") => {
+                let Lit::Bool(lit_bool) = &nv.lit else {
+                    return expected_literal(nv, "a boolean");
+                };
+                is_result_override = Some(lit_bool.value);
+            },
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("stale_while_revalidate") => {
+                let Lit::Bool(lit_bool) = &nv.lit else {
+                    return expected_literal(nv, "a boolean");
+                };
+                stale_while_revalidate = lit_bool.value;
+            },
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("refresh_ahead") => {
+                match parse_seconds(&nv.lit) {
+                    Ok(seconds) => refresh_ahead = Some((seconds, &nv.lit)),
+                    Err(e) => return e.to_compile_error().into(),
+                }
+            },
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("on_error") => {
+                let Lit::Str(lit_str) = &nv.lit else {
+                    return expected_literal(nv, "a string");
+                };
+                if let OnError::Convert = on_error {
+                    return syn::Error::new_spanned(nv, "`cache_fallible` always converts cache errors into the function's error type")
+                        .to_compile_error()
+                        .into();
+                }
+                on_error = match lit_str.value().as_str() {
+                    "propagate" => OnError::Propagate,
+                    "panic" => OnError::Panic,
+                    "fallback" => OnError::Fallback,
+                    other => {
+                        let message = format!("unknown error policy `{}`, expected one of `propagate`, `panic`, `fallback`", other);
+                        return syn::Error::new_spanned(lit_str, message).to_compile_error().into();
+                    }
+                };
+            },
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("cache_errors") => {
+                let Lit::Bool(lit_bool) = &nv.lit else {
+                    return expected_literal(nv, "a boolean");
+                };
+                cache_errors = lit_bool.value.then_some(lit_bool);
+            },
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("error_invalidate_rate") => {
+                match parse_seconds(&nv.lit) {
+                    Ok(seconds) => error_invalidate_rate = seconds,
+                    Err(e) => return e.to_compile_error().into(),
+                }
+            },
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("bypass_env") => {
+                let Lit::Str(lit_str) = &nv.lit else {
+                    return expected_literal(nv, "a string");
+                };
+                bypass_env = lit_str.value();
+            },
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("hash") => {
+                let Lit::Str(lit_str) = &nv.lit else {
+                    return expected_literal(nv, "a string");
+                };
+                let hasher = match lit_str.value().as_str() {
+                    "xxh3" => quote! { disk_cache::hash::Xxh3 },
+                    "sha256" if cfg!(feature = "sha256") => quote! { disk_cache::hash::Sha256 },
+                    "sha256" => {
+                        return syn::Error::new_spanned(lit_str, "`hash = \"sha256\"` requires the `sha256` feature of `disk_cache`")
+                            .to_compile_error()
+                            .into();
+                    }
+                    "blake3" if cfg!(feature = "blake3") => quote! { disk_cache::hash::Blake3 },
+                    "blake3" => {
+                        return syn::Error::new_spanned(lit_str, "`hash = \"blake3\"` requires the `blake3` feature of `disk_cache`")
+                            .to_compile_error()
+                            .into();
+                    }
+                    other => {
+                        let message = format!("unknown hash `{}`, expected one of `xxh3`, `sha256`, `blake3`", other);
+                        return syn::Error::new_spanned(lit_str, message).to_compile_error().into();
+                    }
+                };
+                hash = Some((hasher, lit_str));
+            },
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("format_version") => {
+                let Lit::Int(lit_int) = &nv.lit else {
+                    return expected_literal(nv, "an integer");
+                };
+                match lit_int.base10_parse::<u8>() {
+                    Ok(version) => format_version = Some(version),
+                    Err(e) => return e.to_compile_error().into(),
+                }
+            },
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("integrity") => {
+                let Lit::Str(lit_str) = &nv.lit else {
+                    return expected_literal(nv, "a string");
+                };
+                integrity = Some(match lit_str.value().as_str() {
+                    "crc32" => quote! { disk_cache::integrity::Crc32 },
+                    "blake3" if cfg!(feature = "blake3") => quote! { disk_cache::integrity::Blake3 },
+                    "blake3" => {
+                        return syn::Error::new_spanned(lit_str, "`integrity = \"blake3\"` requires the `blake3` feature of `disk_cache`")
+                            .to_compile_error()
+                            .into();
+                    }
+                    other => {
+                        let message = format!("unknown integrity `{}`, expected one of `crc32`, `blake3`", other);
+                        return syn::Error::new_spanned(lit_str, message).to_compile_error().into();
+                    }
+                });
+            },
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("min_compute_time_ms") => {
+                let Lit::Int(lit_int) = &nv.lit else {
+                    return expected_literal(nv, "an integer");
+                };
+                match lit_int.base10_parse::<u64>() {
+                    Ok(millis) => min_compute_time_ms = Some(millis),
+                    Err(e) => return e.to_compile_error().into(),
+                }
+            },
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("memory_capacity") => {
+                let Lit::Int(lit_int) = &nv.lit else {
+                    return expected_literal(nv, "an integer");
+                };
+                match lit_int.base10_parse::<usize>() {
+                    Ok(capacity) if capacity > 0 => memory_capacity = Some(capacity),
+                    _ => {
+                        return syn::Error::new_spanned(lit_int, "`memory_capacity` must be a positive integer")
+                            .to_compile_error()
+                            .into();
+                    }
+                }
+            },
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("max_entries") => {
+                let Lit::Int(lit_int) = &nv.lit else {
+                    return expected_literal(nv, "an integer");
+                };
+                match lit_int.base10_parse::<usize>() {
+                    Ok(entries) if entries > 0 => max_entries = Some((entries, lit_int)),
+                    _ => {
+                        return syn::Error::new_spanned(lit_int, "`max_entries` must be a positive integer")
+                            .to_compile_error()
+                            .into();
+                    }
+                }
+            },
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("max_bytes") => {
+                let Lit::Int(lit_int) = &nv.lit else {
+                    return expected_literal(nv, "an integer");
+                };
+                match lit_int.base10_parse::<u64>() {
+                    Ok(bytes) => max_bytes = Some((bytes, lit_int)),
+                    Err(e) => return e.to_compile_error().into(),
+                }
+            },
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("touch_on_hit") => {
+                let Lit::Bool(lit_bool) = &nv.lit else {
+                    return expected_literal(nv, "a boolean");
+                };
+                touch_on_hit = lit_bool.value.then_some(lit_bool);
+            },
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("timestamp") => {
+                let Lit::Str(lit_str) = &nv.lit else {
+                    return expected_literal(nv, "a string");
+                };
+                embedded_timestamp = match lit_str.value().as_str() {
+                    "embedded" => true,
+                    "mtime" => false,
+                    other => {
+                        let message = format!("unknown timestamp source `{}`, expected one of `embedded`, `mtime`", other);
+                        return syn::Error::new_spanned(lit_str, message).to_compile_error().into();
+                    }
+                };
+            },
+            NestedMeta::Meta(meta) => {
+                return syn::Error::new_spanned(meta.path(), "unknown cache attribute")
+                    .to_compile_error()
+                    .into();
+            },
+            NestedMeta::Lit(lit) => {
+                return syn::Error::new_spanned(lit, "expected a `name = value` cache attribute")
+                    .to_compile_error()
+                    .into();
+            },
+        }
+    }
+    // the level is checked against the range of the chosen compression
+    if let Some(lit_int) = compress_level {
+        let level = lit_int.base10_parse::<i32>().ok();
+        compression = match (compression, level) {
+            (Compression::Gzip(_), Some(level @ 0..=9)) => Compression::Gzip(level as u32),
+            (Compression::Zstd(_), Some(level @ 1..=22)) => Compression::Zstd(level),
+            (compression, _) => {
+                let message = match compression {
+                    Compression::None => "`compress_level` requires `compress`",
+                    Compression::Gzip(_) => "`compress_level` must be between 0 and 9 for gzip",
+                    Compression::Zstd(_) => "`compress_level` must be between 1 and 22 for zstd",
+                };
+                return syn::Error::new_spanned(lit_int, message).to_compile_error().into();
+            }
+        };
+    }
+    if let (Some(lit_int), Compression::None) = (compress_min_bytes, &compression) {
+        return syn::Error::new_spanned(lit_int, "`compress_min_bytes` requires `compress`").to_compile_error().into();
+    }
+    // attributes naming a function take a path rather than a literal
+    for (name, path) in paths {
+        if name == "on_write_error" {
+            on_write_error = Some(path);
+        } else if name == "on_event" {
+            on_event = Some(path);
+        } else if name == "cache_if" {
+            cache_if = Some(path);
+        } else if name == "serialize_with" {
+            serialize_with = Some(path);
+        } else if name == "deserialize_with" {
+            deserialize_with = Some(path);
+        } else if name == "owned_as" {
+            owned_as = Some(path);
+        } else if name == "read_as" {
+            read_as = Some(path);
+        } else if name == "validate" {
+            validate = Some(path);
+        } else if name == "concrete" {
+            concrete = Some(path);
+        } else if name == "config" {
+            config = Some(path);
+        } else {
+            return syn::Error::new_spanned(path, format!("`{}` expects a literal value", name))
+                .to_compile_error()
+                .into();
+        }
+    }
+    // a custom encoding replaces the serde formats, and needs both directions
+    match (serialize_with, deserialize_with) {
+        (Some(serialize), Some(deserialize)) => {
+            if let Some(lit_str) = format_lit {
+                return syn::Error::new_spanned(lit_str, "`format` cannot be combined with `serialize_with` and `deserialize_with`")
+                    .to_compile_error()
+                    .into();
+            }
+            format = Format::Custom { serialize, deserialize };
+        },
+        (Some(path), None) => {
+            return syn::Error::new_spanned(path, "`serialize_with` requires `deserialize_with`").to_compile_error().into();
+        },
+        (None, Some(path)) => {
+            return syn::Error::new_spanned(path, "`deserialize_with` requires `serialize_with`").to_compile_error().into();
+        },
+        (None, None) => {},
+    }
+    // a stale entry is refreshed by writing it again
+    if let (Some(lit_bool), true) = (read_only, stale_while_revalidate || refresh_ahead.is_some()) {
+        return syn::Error::new_spanned(lit_bool, "`read_only` cannot be combined with `stale_while_revalidate` or `refresh_ahead`")
+            .to_compile_error()
+            .into();
+    }
+    // the jitter of each entry is stored next to its write time
+    if let (Some((_, lit)), false) = (invalidate_jitter, embedded_timestamp) {
+        return syn::Error::new_spanned(lit, "`invalidate_jitter` cannot be combined with `timestamp = \"mtime\"`")
+            .to_compile_error()
+            .into();
+    }
+    // with `owned_as`, a borrowed return type is replaced by its owned form, which is what the function then returns
+    let owned_output;
+    let mut borrowed_type = None;
+    let func_output = match (&owned_as, func_output) {
+        (Some(owned), ReturnType::Type(arrow, ty)) if matches!(**ty, Type::Reference(_)) => {
+            borrowed_type = Some(ty);
+            owned_output = ReturnType::Type(*arrow, Box::new(syn::parse_quote! { #owned }));
+            &owned_output
+        },
+        (Some(owned), _) => {
+            return syn::Error::new_spanned(owned, "`owned_as` requires a reference return type, such as `&[T]` or `&str`")
+                .to_compile_error()
+                .into();
+        },
+        (None, func_output) => func_output,
+    };
+    // with `concrete`, the boxed trait object is cached as the concrete type, and only boxed into it when returned
+    let surface_output = func_output;
+    let mut func_output = func_output.clone();
+    let mut trait_object = None;
+    if let Some(concrete) = &concrete {
+        if let ReturnType::Type(_, ty) = &mut func_output {
+            trait_object = replace_trait_object(ty, concrete);
+        }
+        if trait_object.is_none() {
+            return syn::Error::new_spanned(concrete, "`concrete` requires a `Box<dyn Trait>`, `Result<Box<dyn Trait>, E>` or `Option<Box<dyn Trait>>` return type")
+                .to_compile_error()
+                .into();
+        }
+    }
+    // with `collect`, the items of the returned stream are cached as a `Vec`, and streamed back out of it
+    if let Some(lit_bool) = collect {
+        let item_type = match &func_output {
+            ReturnType::Type(_, ty) => stream_item(ty),
+            ReturnType::Default => None,
+        };
+        let conflict = match item_type {
+            _ if matches!(io, Io::Std) => Some("`collect` requires `cache_async`"),
+            None => Some("`collect` requires an `impl Stream<Item = T>` return type"),
+            Some(_) => None,
+        };
+        if let Some(message) = conflict {
+            return syn::Error::new_spanned(lit_bool, message).to_compile_error().into();
+        }
+        if let (ReturnType::Type(_, ty), Some(item_type)) = (&mut func_output, item_type) {
+            **ty = syn::parse_quote! { Vec<#item_type> };
+        }
+    }
+    let func_output = &func_output;
+    let func_type = match func_output {
+        syn::ReturnType::Type(_, t) => t,
+        syn::ReturnType::Default => {
+            return syn::Error::new_spanned(&input.sig, "cached functions must have a return type")
+                .to_compile_error()
+                .into();
+        }
+    };
+    let surface_type = match surface_output {
+        ReturnType::Type(_, t) => t,
+        ReturnType::Default => func_type,
+    };
+    // a unit return type has nothing worth caching, so the function is left as it is, with a warning
+    if matches!(&**func_type, Type::Tuple(tuple) if tuple.elems.is_empty()) {
+        let message = "caching a function returning `()` does nothing useful";
+        if deny_unit {
+            return syn::Error::new_spanned(func_type, message).to_compile_error().into();
+        }
+        let mut input = input.clone();
+        let warning = quote_spanned! { func_type.span() =>
+            {
+                #[deprecated(note = "caching a function returning `()` does nothing useful, remove the cache attribute")]
+                struct UnitReturnIsNotCached;
+                let _ = UnitReturnIsNotCached;
+            }
+        };
+        input.block.stmts.insert(0, syn::parse_quote! { #warning });
+        return quote! { #input }.into();
+    }
+    // a type alias of a `Result` cannot be recognized from its name, so `is_result = true` tells the macro, which then
+    // names its parts through `disk_cache::result::ResultType`, and `is_result = false` caches a `Result` whole
+    let alias_parts: (Type, Type) = (
+        syn::parse_quote! { <#func_type as disk_cache::result::ResultType>::Ok },
+        syn::parse_quote! { <#func_type as disk_cache::result::ResultType>::Err },
+    );
+    let return_shape = match (is_result_override, return_shape(func_output)) {
+        (Some(true), ReturnShape::Option(_) | ReturnShape::Plain) => ReturnShape::Result(&alias_parts.0, &alias_parts.1),
+        (Some(false), ReturnShape::Result(..)) => ReturnShape::Plain,
+        (_, return_shape) => return_shape,
+    };
+    // with `cache_errors`, the whole Result is cached, so it is treated like any other return type
+    let is_result = matches!(return_shape, ReturnShape::Result(..)) && cache_errors.is_none();
+    if let (ReturnShape::Option(_) | ReturnShape::Plain, Some(lit_bool)) = (&return_shape, cache_errors) {
+        return syn::Error::new_spanned(lit_bool, "`cache_errors` requires a `Result` return type")
+            .to_compile_error()
+            .into();
+    }
+    // with `cache_fallible`, errors of the cache layer are returned as the function's own error type
+    let convert_type = match (&on_error, &return_shape) {
+        (OnError::Convert, ReturnShape::Result(_, err_type)) => Some(*err_type),
+        (OnError::Convert, _) => {
+            return syn::Error::new_spanned(func_type, "`cache_fallible` requires a `Result` return type")
+                .to_compile_error()
+                .into();
+        }
+        _ => None,
+    };
+    let fs = io.fs();
+    let error = quote! { disk_cache::CacheError };
+    let asyncness = io.asyncness();
+    let dot_await = io.dot_await();
+    let exists = match backend {
+        Backend::Files | Backend::Tiered(_) => io.exists(quote! { &cache_path }),
+        Backend::Sled(_) => quote! { disk_cache::backend::sled_contains(&db_root, &cache_path) },
+        Backend::Redis(_) => quote! { disk_cache::backend::redis_contains(redis_url, &cache_path).await },
+    };
+    // the body is checked against the return type where it can be named, so that a mismatch is reported at the body's
+    // own expression rather than at the attribute
+    let body_type = match (future_output, &owned_as, collect) {
+        (None, None, None) => Some(&**func_type),
+        _ => None,
+    };
+    // the body is moved into `compute`, which runs it on a miss, or in the background to refresh a hit
+    let compute = match future_output {
+        // the body evaluates to the future, so it is awaited rather than wrapped in another `async` block
+        Some(_) => quote_spanned! { func_body.span() => move || #func_body },
+        None => io.compute(func_body, body_type),
+    };
+    let compute = quote! { let compute = #compute; };
+    let body_call = quote! { compute()#dot_await };
+    let body_call = match (&owned_as, borrowed_type) {
+        (Some(owned), Some(borrowed)) => quote! { <#owned as From<#borrowed>>::from(#body_call) },
+        _ => body_call,
+    };
+    let body_call = match collect {
+        Some(_) => quote! { disk_cache::stream::collect(#body_call).await },
+        None => body_call,
+    };
+    // in the `propagate` mode the return type is wrapped in a Result, so every return has to be wrapped in `Ok`
+    let wrap_return = |value: proc_macro2::TokenStream| match on_error {
+        OnError::Propagate => quote! { Ok(#value) },
+        OnError::Panic | OnError::Fallback | OnError::Convert => value,
+    };
+    let return_type = match on_error {
+        OnError::Propagate => quote! { Result<#surface_type, #error> },
+        OnError::Panic | OnError::Fallback | OnError::Convert => quote! { #surface_type },
+    };
+    // the wrapping `Result` is easy to drop by mistake, which would silently discard errors of the cache layer
+    let must_use = match on_error {
+        OnError::Propagate => quote! { #[must_use = "the result may be an error of the cache layer, which should be handled"] },
+        OnError::Panic | OnError::Fallback | OnError::Convert => quote! {},
+    };
+    // with `min_compute_time_ms`, the body is timed so that values computed faster than that are not stored
+    let start_timer = if min_compute_time_ms.is_some() {
+        quote! { let compute_started = std::time::Instant::now(); }
+    } else {
+        quote! {}
+    };
+    // here, we want to check if the return type is a Result type. Only then we can use the ? operator
+    let mut calling_code = quote! { 
+        #start_timer
+        let result: #func_type = #body_call;
+    };
+    if is_result{
+        let early_return = wrap_return(quote! { Err(e) });
+        calling_code = quote! { 
+            #start_timer
+            let result: #func_type = #body_call;
+            if let Err(e) = result {
+                return #early_return;
+            }
+            let result = result.unwrap();
+        };
+    }
+    // One other thing is that if there is a Result type, we need to return Ok(result) instead of result on cache hit
+    let mut return_call = quote! { result };
+    let mut whole_return = quote! { result };
+    if let Some(trait_object) = &trait_object {
+        whole_return = match return_shape {
+            ReturnShape::Plain => quote! { result as #trait_object },
+            ReturnShape::Result(..) | ReturnShape::Option(_) => quote! { result.map(|value| value as #trait_object) },
+        };
+        return_call = match return_shape {
+            ReturnShape::Result(..) if is_result => quote! { result as #trait_object },
+            _ => whole_return.clone(),
+        };
+    }
+    if collect.is_some() {
+        return_call = quote! { disk_cache::stream::iter(result) };
+        whole_return = return_call.clone();
+    }
+    if is_result{
+        return_call = quote! { Ok(#return_call) };
+    }
+    let cached_type = match return_shape {
+        ReturnShape::Result(ok_type, _) if is_result => ok_type,
+        _ => func_type,
+    };
+    // also, if result type, we only need to cache the Ok part of the result: thus, we check if Ok part is serializable.
+    // An Option is cached whole, but only needs its inner type to be serializable
+    let bounded_type = match return_shape {
+        ReturnShape::Option(some_type) => some_type,
+        _ => cached_type,
+    };
+    // bytes are stored as they are, unless a format is asked for
+    if format_lit.is_none() && matches!(format, Format::Json) && is_byte_vec(cached_type) {
+        format = Format::Raw;
+    }
+    if let (Some(lit_bool), false) = (pretty, matches!(format, Format::Json | Format::Auto)) {
+        return syn::Error::new_spanned(lit_bool, "`pretty` requires `format = \"json\"` or `format = \"auto\"`").to_compile_error().into();
+    }
+    // with `deterministic`, hashed collections are written through their sorted counterparts, which serialize the same
+    let mut written_payload = quote! { result };
+    if let Some(lit_bool) = deterministic {
+        let sorted = match hashed_collection(cached_type) {
+            Some("HashMap") => quote! { std::collections::BTreeMap<_, _> },
+            Some(_) => quote! { std::collections::BTreeSet<_> },
+            None => {
+                return syn::Error::new_spanned(lit_bool, "`deterministic` requires a `HashMap` or `HashSet` cached type")
+                    .to_compile_error()
+                    .into();
+            }
+        };
+        if matches!(format, Format::Custom { .. }) {
+            return syn::Error::new_spanned(lit_bool, "`deterministic` cannot be combined with `serialize_with`")
+                .to_compile_error()
+                .into();
+        }
+        written_payload = quote! { result.iter().collect::<#sorted>() };
+    }
+    // the functions of a custom encoding take the type as it is, without serde
+    let mut where_predicates = Vec::new();
+    if !matches!(format, Format::Custom { .. } | Format::Raw) {
+        // serde can only deserialize borrowed data from the bytes it was read from, which do not outlive the call
+        if let Some(reference) = find_reference(bounded_type) {
+            return syn::Error::new_spanned(reference, "cached return types cannot borrow, use an owned type such as `String` or `Cow<'static, str>`, or name it with `owned_as = Vec<T>` to have it returned instead")
+                .to_compile_error()
+                .into();
+        }
+        // trait objects have no type to deserialize into, unless one is given with `concrete`
+        if let Some(trait_object) = find_trait_object(bounded_type) {
+            return syn::Error::new_spanned(trait_object, "cached return types cannot be trait objects, name the type to cache instead with `concrete = Type`")
+                .to_compile_error()
+                .into();
+        }
+        // rustc does not check higher-ranked bounds on the definition, so that a type missing serde's traits is only
+        // reported by `Cacheable`, once for all the functions and at the return type, rather than with serde's impls
+        where_predicates.push(quote! { for<'__cache> #bounded_type: serde::Serialize + serde::de::DeserializeOwned });
+        where_predicates.push(quote_spanned! { bounded_type.span() => #bounded_type: disk_cache::cacheable::Cacheable });
+    }
+    if memory_capacity.is_some() {
+        where_predicates.push(quote! { #bounded_type: Clone });
+    }
+    // with `read_as`, entries of an older type are read as well, and converted into the cached type
+    if let Some(old_type) = &read_as {
+        if matches!(format, Format::Custom { .. } | Format::Raw) {
+            return syn::Error::new_spanned(old_type, "`read_as` requires a serde `format`, not `serialize_with` nor a cached `Vec<u8>`")
+                .to_compile_error()
+                .into();
+        }
+        where_predicates.push(quote! { #old_type: serde::de::DeserializeOwned });
+        where_predicates.push(quote! { #cached_type: From<#old_type> });
+    }
+    if let Some(err_type) = convert_type {
+        where_predicates.push(quote! { #err_type: From<std::io::Error> });
+    }
+    // the user's own bounds are kept alongside the ones the cache needs
+    let func_generics = &input.sig.generics;
+    let user_where_clause = &func_generics.where_clause;
+    if let Some(user_where_clause) = user_where_clause {
+        where_predicates.splice(0..0, user_where_clause.predicates.iter().map(|predicate| quote! { #predicate }));
+    }
+    let where_clause = quote! { where #(#where_predicates),* };
+    if let Some(lit_str) = never_expires {
+        let conflict = if ttl_arg.is_some() {
+            Some("`invalidate_rate = \"never\"` cannot be combined with a `#[ttl]` argument")
+        } else if stale_while_revalidate || refresh_ahead.is_some() {
+            Some("`invalidate_rate = \"never\"` cannot be combined with `stale_while_revalidate` or `refresh_ahead`")
+        } else {
+            None
+        };
+        if let Some(message) = conflict {
+            return syn::Error::new_spanned(lit_str, message).to_compile_error().into();
+        }
+    }
+    let invalidate_rate = match (&ttl_arg, invalidate_rate, &config) {
+        (Some((_, seconds)), _, _) => seconds.clone(),
+        (None, Some(seconds), _) => quote! { #seconds },
+        (None, None, Some(config)) => quote! { (#config.invalidate_rate as i64) },
+        (None, None, None) => quote! { 3600 },
+    };
+    // how long a value, as opposed to a cached error, stays valid
+    let value_expiry = match never_expires {
+        Some(_) => quote! { chrono::Duration::MAX },
+        None => quote! { chrono::Duration::seconds(#invalidate_rate) },
+    };
+    // cached errors expire after their own, usually shorter, invalidate rate, so the expiry of the entry read into
+    // `result` depends on it
+    let mut max_invalidate_rate = invalidate_rate.clone();
+    let mut entry_expiry = value_expiry.clone();
+    if cache_errors.is_some() {
+        max_invalidate_rate = quote! { std::cmp::max(#invalidate_rate, #error_invalidate_rate) };
+        entry_expiry = quote! {
+            match &result {
+                Ok(_) => #value_expiry,
+                Err(_) => chrono::Duration::seconds(#error_invalidate_rate),
+            }
+        };
+    }
+
+    // the fixed directories of the cache root, and the rest starting at the first placeholder
+    let (cache_prefix, mut cache_rest) = split_template(&cache_path);
+    let has_placeholders = !cache_rest.is_empty();
+    // the function's name, or `cache_id`, and `namespace` are inserted between the fixed directories and the interpolated
+    // ones, so that functions sharing a cache root do not overwrite each other's entries
+    if !flat && cache_id.is_none() {
+        cache_id = Some(func_name.to_string());
+    }
+    if cache_id.is_some() || namespace.is_some() {
+        cache_rest = [cache_id.as_deref(), namespace.as_deref(), Some(cache_rest.as_str())]
+            .into_iter()
+            .flatten()
+            .filter(|segment| !segment.is_empty())
+            .collect::<Vec<_>>()
+            .join("/");
+        cache_path = if cache_prefix.is_empty() {
+            cache_rest.clone()
+        } else {
+            format!("{}/{}", cache_prefix, cache_rest)
+        };
+    }
+    // the directories of this function's entries that come before any placeholder, which `_clear_all` removes
+    let (function_prefix, function_template) = split_template(&cache_path);
+    let function_rest = function_prefix.strip_prefix(cache_prefix.as_str()).unwrap_or_default().trim_start_matches('/').to_string();
+    // with `key_includes_body`, a hash of the body follows those directories, so that editing the body moves the entries
+    // elsewhere while `_clear_all` still removes those of every version
+    if key_includes_body {
+        let body_hash = body_hash(func_body);
+        cache_path = [function_prefix.as_str(), body_hash.as_str(), function_template.as_str()]
+            .into_iter()
+            .filter(|segment| !segment.is_empty())
+            .collect::<Vec<_>>()
+            .join("/");
+        cache_rest = cache_path.strip_prefix(cache_prefix.as_str()).unwrap_or(&cache_path).trim_start_matches('/').to_string();
+    }
+    // the fixed directories are plain paths from here on
+    let cache_prefix = unescape_braces(&cache_prefix);
+    let function_prefix = unescape_braces(&function_prefix);
+    let function_rest = unescape_braces(&function_rest);
+    // placeholders may only name arguments of the function
+    let params: Vec<String> = func_args
+        .iter()
+        .filter_map(|arg| match arg {
+            syn::FnArg::Typed(pat_type) => match &*pat_type.pat {
+                syn::Pat::Ident(pat_ident) => Some(pat_ident.ident.to_string()),
+                _ => None,
+            },
+            // fields of the receiver can be interpolated as well, e.g. `{self.id}`
+            syn::FnArg::Receiver(_) => Some(String::from("self")),
+        })
+        .collect();
+    let full_dir = match template_format(&cache_path, &params) {
+        Ok(full_dir) => full_dir,
+        Err(message) => return syn::Error::new(cache_root_span, message).to_compile_error().into(),
+    };
+    // the default root and the home directory depend on the machine the function runs on, so they are only resolved there
+    let runtime_root = if !has_cache_root {
+        Some(quote! { disk_cache::file::default_cache_root() })
+    } else if starts_at_home(&cache_prefix) {
+        Some(quote! { disk_cache::file::expand_home(#cache_prefix) })
+    } else {
+        None
+    };
+    // with `cache_root_env`, the non-interpolated prefix of the cache root can be replaced at runtime, and with `config`
+    // but without `cache_root`, it is replaced by the root of the configuration
+    let relocated_root = match (&cache_root_env, &config, &runtime_root) {
+        (Some(cache_root_env), Some(config), _) if !has_cache_root => {
+            Some(quote! { Some(std::env::var(#cache_root_env).unwrap_or_else(|_| #config.cache_root.to_string())) })
+        },
+        (None, Some(config), _) if !has_cache_root => Some(quote! { Some(#config.cache_root.to_string()) }),
+        (Some(cache_root_env), _, Some(runtime_root)) => Some(quote! { Some(std::env::var(#cache_root_env).unwrap_or_else(|_| #runtime_root)) }),
+        (Some(cache_root_env), _, None) => Some(quote! { std::env::var(#cache_root_env).ok() }),
+        (None, _, Some(runtime_root)) => Some(quote! { Some(#runtime_root) }),
+        (None, _, None) => None,
+    };
+    let mut root_dir = full_dir.clone();
+    if let Some(relocated_root) = &relocated_root {
+        let rest = &cache_rest;
+        let rest_dir = match template_format(rest, &params) {
+            Ok(rest_dir) => rest_dir,
+            Err(message) => return syn::Error::new(cache_root_span, message).to_compile_error().into(),
+        };
+        root_dir = quote! {
+            match #relocated_root {
+                Some(root) => format!("{}/{}", root.trim_end_matches('/'), #rest_dir),
+                None => #full_dir,
+            }
+        };
+        if rest.is_empty() {
+            root_dir = quote! { #relocated_root.unwrap_or_else(|| #full_dir) };
+        }
+    }
+    // with `key = "args"`, every argument is fed to a hasher and the digest becomes a subdirectory of the cache root
+    // interpolated values are made filesystem-safe, so that they can neither escape the cache root nor collide
+    let sanitize_key = quote! {
+        let sanitize_key = disk_cache::file::sanitize_key;
+    };
+    let mut key_code = quote! {
+        #sanitize_key
+        let cache_dir: String = #root_dir;
+    };
+    if let (Some((_, lit_str)), false) = (&hash, key_args) {
+        return syn::Error::new_spanned(lit_str, "`hash` requires `key = \"args\"`").to_compile_error().into();
+    }
+    if key_args {
+        let hasher = match hash {
+            Some((hasher, _)) => hasher,
+            None => quote! { disk_cache::hash::Xxh3 },
+        };
+        let mut arg_names = match arg_idents(func_args) {
+            Ok(arg_names) => arg_names,
+            Err(e) => return e.to_compile_error().into(),
+        };
+        // the ttl decides how long an entry stays valid, not which entry is read
+        if let Some((ttl_ident, _)) = &ttl_arg {
+            arg_names.retain(|arg_name| *arg_name != ttl_ident);
+        }
+        key_code = quote! {
+            #sanitize_key
+            let cache_dir: String = {
+                let mut hasher = <#hasher>::default();
+                #( std::hash::Hash::hash(&#arg_names, &mut hasher); )*
+                format!("{}/{}", #root_dir, hasher.hex_digest())
+            };
+        };
+    }
+    // with `max_entries`, the directory holding one key is an entry, and its siblings are the other entries
+    if let Some((_, lit_int)) = max_entries {
+        if file_name_lit.is_some() {
+            return syn::Error::new_spanned(lit_int, "`max_entries` cannot be combined with `file_name`")
+                .to_compile_error()
+                .into();
+        }
+        if !key_args && !has_placeholders {
+            return syn::Error::new_spanned(lit_int, "`max_entries` requires `key = \"args\"` or a `cache_root` with placeholders")
+                .to_compile_error()
+                .into();
+        }
+        key_code = quote! {
+            #key_code
+            let entry_dir: String = cache_dir.clone();
+        };
+    }
+    // the fixed directories of the cache root, as relocated by `cache_root_env` or `config`
+    let fixed_root = match &relocated_root {
+        Some(relocated_root) => quote! {
+            #relocated_root.map(|root| root.trim_end_matches('/').to_string()).unwrap_or_else(|| #cache_prefix.to_string())
+        },
+        None => quote! { #cache_prefix.to_string() },
+    };
+    let has_fixed_root = !cache_prefix.trim_matches('/').trim_matches('.').is_empty();
+    // with `max_bytes`, the budget covers the whole tree below the non-interpolated prefix of the cache root
+    if let Some((_, lit_int)) = max_bytes {
+        if !has_fixed_root {
+            return syn::Error::new_spanned(lit_int, "`max_bytes` requires `cache_root` to start with a fixed directory")
+                .to_compile_error()
+                .into();
+        }
+        key_code = quote! {
+            #key_code
+            let budget_root: String = #fixed_root;
+        };
+    }
+    // with `read_fallback`, a miss is also looked up below another root, in place of the fixed directories
+    let mut fallback_root = None;
+    if let Some(lit_str) = read_fallback {
+        let conflict = if !matches!(backend, Backend::Files) {
+            Some("`read_fallback` requires the `\"files\"` backend")
+        } else if !has_fixed_root {
+            Some("`read_fallback` requires `cache_root` to start with a fixed directory")
+        } else {
+            None
+        };
+        if let Some(message) = conflict {
+            return syn::Error::new_spanned(lit_str, message).to_compile_error().into();
+        }
+        let root = lit_str.value().trim_end_matches('/').to_string();
+        fallback_root = Some(if starts_at_home(&root) { quote! { disk_cache::file::expand_home(#root) } } else { quote! { #root } });
+    }
+    // with the sled backend, the database lives in the fixed directories of the cache root, keyed by the cache path
+    if let Backend::Sled(lit_str) = backend {
+        let conflict = if !has_fixed_root {
+            Some("`backend = \"sled\"` requires `cache_root` to start with a fixed directory")
+        } else if !embedded_timestamp {
+            Some("`backend = \"sled\"` cannot be combined with `timestamp = \"mtime\"`")
+        } else if max_entries.is_some() || max_bytes.is_some() {
+            Some("`backend = \"sled\"` cannot be combined with `max_entries` or `max_bytes`")
+        } else {
+            None
+        };
+        if let Some(message) = conflict {
+            return syn::Error::new_spanned(lit_str, message).to_compile_error().into();
+        }
+        key_code = quote! {
+            #key_code
+            let db_root: String = #fixed_root;
+        };
+    }
+    // with the redis backend, entries are keyed by the cache path in the server at `redis_url`
+    if let Backend::Redis(lit_str) = backend {
+        let conflict = if !matches!(io, Io::Tokio) {
+            Some("`backend = \"redis\"` requires `cache_async` with the tokio runtime")
+        } else if redis_url.is_none() {
+            Some("`backend = \"redis\"` requires `redis_url`")
+        } else if !embedded_timestamp {
+            Some("`backend = \"redis\"` cannot be combined with `timestamp = \"mtime\"`")
+        } else if max_entries.is_some() || max_bytes.is_some() {
+            Some("`backend = \"redis\"` cannot be combined with `max_entries` or `max_bytes`")
+        } else {
+            None
+        };
+        if let Some(message) = conflict {
+            return syn::Error::new_spanned(lit_str, message).to_compile_error().into();
+        }
+        key_code = quote! {
+            #key_code
+            let redis_url: &str = #redis_url;
+        };
+    }
+    // with the tiered backend, files are written in front of the same entries in the server at `redis_url`
+    if let Backend::Tiered(lit_str) = backend {
+        let conflict = if !matches!(io, Io::Tokio) {
+            Some("`backend = \"files+redis\"` requires `cache_async` with the tokio runtime")
+        } else if redis_url.is_none() {
+            Some("`backend = \"files+redis\"` requires `redis_url`")
+        } else if !embedded_timestamp {
+            Some("`backend = \"files+redis\"` cannot be combined with `timestamp = \"mtime\"`")
+        } else if read_only.is_some() {
+            Some("`backend = \"files+redis\"` cannot be combined with `read_only`")
+        } else {
+            None
+        };
+        if let Some(message) = conflict {
+            return syn::Error::new_spanned(lit_str, message).to_compile_error().into();
+        }
+        key_code = quote! {
+            #key_code
+            let redis_url: &str = #redis_url;
+        };
+    }
+    // the lock file lives next to the cache file, which must be writable
+    if let Some(lit_bool) = lock {
+        let conflict = if !matches!(backend, Backend::Files | Backend::Tiered(_)) {
+            Some("`lock` requires `backend = \"files\"` or `\"files+redis\"`")
+        } else if read_only.is_some() {
+            Some("`lock` cannot be combined with `read_only`")
+        } else {
+            None
+        };
+        if let Some(message) = conflict {
+            return syn::Error::new_spanned(lit_bool, message).to_compile_error().into();
+        }
+    }
+    // touching a file refreshes its mtime, which would otherwise be its write time
+    if let Some(lit_bool) = touch_on_hit {
+        let conflict = if !matches!(backend, Backend::Files | Backend::Tiered(_)) {
+            Some("`touch_on_hit` requires `backend = \"files\"` or `\"files+redis\"`")
+        } else if !embedded_timestamp {
+            Some("`touch_on_hit` cannot be combined with `timestamp = \"mtime\"`")
+        } else if read_only.is_some() {
+            Some("`touch_on_hit` cannot be combined with `read_only`")
+        } else {
+            None
+        };
+        if let Some(message) = conflict {
+            return syn::Error::new_spanned(lit_bool, message).to_compile_error().into();
+        }
+    }
+    // each instantiation of a generic function gets its own subdirectory, named after its type and const arguments
+    let generic_args: Vec<proc_macro2::TokenStream> = input.sig.generics.params.iter().filter_map(|param| match param {
+        syn::GenericParam::Type(type_param) => {
+            let ident = &type_param.ident;
+            Some(quote! { std::any::type_name::<#ident>().to_string() })
+        },
+        syn::GenericParam::Const(const_param) => {
+            let ident = &const_param.ident;
+            Some(quote! { #ident.to_string() })
+        },
+        syn::GenericParam::Lifetime(_) => None,
+    }).collect();
+    if !generic_args.is_empty() {
+        key_code = quote! {
+            #key_code
+            let cache_dir: String = format!("{}/{}", cache_dir, sanitize_key([#(#generic_args),*].join(",")));
+        };
+    }
+    if let (Some(_), Some(type_param)) = (memory_capacity, input.sig.generics.type_params().next()) {
+        return syn::Error::new_spanned(type_param, "`memory_capacity` is not supported on generic functions")
+            .to_compile_error()
+            .into();
+    }
+    // figure out the header - depends on pub
+    let func_vis = &input.vis;
+    // with `file_name`, the name of the cache file is interpolated like the cache root, and used as it is
+    let file_name = match file_name_lit {
+        Some(lit_str) => match template_format(&lit_str.value(), &params) {
+            Ok(file_name) => file_name,
+            Err(message) => return syn::Error::new_spanned(lit_str, message).to_compile_error().into(),
+        },
+        None => {
+            let file_name = format!("data.{}{}", format.extension(), compression.extension());
+            quote! { #file_name }
+        }
+    };
+    // with `format = "auto"`, the files of every format are looked for, in place of the one that is written
+    let mut auto_file_names = Vec::new();
+    if matches!(format, Format::Auto) {
+        if let Some(lit_str) = file_name_lit {
+            return syn::Error::new_spanned(lit_str, "`file_name` cannot be combined with `format = \"auto\"`")
+                .to_compile_error()
+                .into();
+        }
+        auto_file_names = Format::SERDE
+            .iter()
+            .map(|format| format!("data.{}{}", format.extension(), compression.extension()))
+            .collect();
+    }
+    // reads `cache_path` into `data`, and then undoes the layers written around the serialized entry, outermost first
+    let read_data = match backend {
+        Backend::Files | Backend::Tiered(_) => quote! { #fs::read(&cache_path)#dot_await },
+        Backend::Sled(_) => quote! { disk_cache::backend::sled_get(&db_root, &cache_path) },
+        Backend::Redis(_) => quote! { disk_cache::backend::redis_get(redis_url, &cache_path).await },
+    };
+    let mut read_bytes = quote! { let data = #read_data?; };
+    // with `integrity`, the checksum at the end of the entry is checked even before the header
+    if let Some(checksum) = &integrity {
+        read_bytes = quote! {
+            #read_bytes
+            let data = disk_cache::integrity::strip_footer::<#checksum>(data)?;
+        };
+    }
+    // with `format_version`, the header in front of the entry is checked before anything else
+    if let Some(version) = format_version {
+        read_bytes = quote! {
+            #read_bytes
+            let data = disk_cache::file::strip_header(#version, data)?;
+        };
+    }
+    // with `encrypt`, the entry is decrypted before it is decompressed, with the `key` loaded by the caller
+    let mut load_key = quote! {};
+    let mut load_key_is_cached = quote! {};
+    if encrypt {
+        read_bytes = quote! {
+            #read_bytes
+            let data = disk_cache::crypto::decrypt(&key, &data)?;
+        };
+        // without a usable key, every entry is a miss
+        load_key = quote! {
+            let Some(key) = disk_cache::crypto::key(#encrypt_key_env) else {
+                return Ok(None);
+            };
+        };
+        load_key_is_cached = quote! {
+            let Some(key) = disk_cache::crypto::key(#encrypt_key_env) else {
+                return Ok(false);
+            };
+        };
+    }
+    // with `compress_min_bytes`, a leading byte tells whether the rest is compressed
+    let runtime_compression = compression.runtime();
+    let min_bytes = match compress_min_bytes.map(|lit_int| lit_int.base10_parse::<usize>()) {
+        Some(Ok(min_bytes)) => quote! { Some(#min_bytes) },
+        _ => quote! { None },
+    };
+    let flagged = compress_min_bytes.is_some();
+    let compressed = !matches!(compression, Compression::None) || flagged;
+    if compressed {
+        read_bytes = quote! {
+            #read_bytes
+            let data = #runtime_compression.decompress(#flagged, data)?;
+        };
+    }
+    // the write time either comes from a stamp in the entry, with the jitter drawn by `invalidate_jitter`, or from the
+    // file's mtime
+    let jittered = invalidate_jitter.is_some();
+    let stamp_now = match invalidate_jitter {
+        Some((seconds, _)) => quote! { disk_cache::codec::Stamp::now(Some(#seconds)) },
+        None => quote! { disk_cache::codec::Stamp::now(None) },
+    };
+    let read_mtime = quote! {
+        let last_written = chrono::DateTime::<chrono::Utc>::from(#fs::metadata(&cache_path)#dot_await?.modified()?);
+    };
+    // `read_entry` reads `result` and `last_written`, `read_stamp` only `last_written`, and `serialize` is the bytes
+    // written for `result`
+    let (read_entry, read_stamp, serialize) = match format.runtime() {
+        Some(runtime_format) => {
+            // with `format = "auto"`, each file is read in the format named by its extension
+            let read_format = match format {
+                Format::Auto => quote! { disk_cache::codec::Format::of_path(&cache_path)? },
+                _ => runtime_format.clone(),
+            };
+            // indented output only changes the whitespace, which every json reader skips
+            let write_format = match pretty {
+                Some(_) => quote! { disk_cache::codec::Format::PrettyJson },
+                None => runtime_format,
+            };
+            // with `read_as`, an entry that is not of the cached type is read as the older one, and converted
+            let (decode, deserialize) = match &read_as {
+                Some(old_type) => (
+                    quote! { disk_cache::codec::decode_as::<#cached_type, #old_type> },
+                    quote! { deserialize_as::<#cached_type, #old_type> },
+                ),
+                None => (quote! { disk_cache::codec::decode::<#cached_type> }, quote! { deserialize::<#cached_type> }),
+            };
+            if embedded_timestamp {
+                (
+                    quote! {
+                        #read_bytes
+                        let (stamp, result) = #decode(#read_format, #jittered, data)?;
+                        let last_written = stamp.last_written();
+                    },
+                    quote! {
+                        #read_bytes
+                        let last_written = disk_cache::codec::decode_stamp(#read_format, #jittered, data)?.last_written();
+                    },
+                    quote! { disk_cache::codec::encode(#write_format, #stamp_now, &#written_payload) },
+                )
+            } else {
+                (
+                    quote! {
+                        #read_mtime
+                        #read_bytes
+                        let result = #read_format.#deserialize(data)?;
+                    },
+                    read_mtime,
+                    quote! { #write_format.serialize(&#written_payload) },
+                )
+            }
+        },
+        // a custom encoding, or a cached `Vec<u8>`, only handles the payload, so the stamp is written in front of it
+        None => {
+            let (decode_payload, encode_payload) = match &format {
+                Format::Custom { serialize, deserialize } => (
+                    quote! { #deserialize(payload).map_err(|e| #error::Deserialize(e.into()))? },
+                    quote! { #serialize(&result) },
+                ),
+                _ => (quote! { payload.to_vec() }, quote! { result.to_vec() }),
+            };
+            if embedded_timestamp {
+                (
+                    quote! {
+                        #read_bytes
+                        let (stamp, payload) = disk_cache::codec::split_stamp(#jittered, &data)?;
+                        let result: #cached_type = #decode_payload;
+                        let last_written = stamp.last_written();
+                    },
+                    quote! {
+                        #read_bytes
+                        let last_written = disk_cache::codec::split_stamp(#jittered, &data)?.0.last_written();
+                    },
+                    quote! { Ok::<Vec<u8>, #error>(disk_cache::codec::with_stamp(#stamp_now, &#encode_payload)) },
+                )
+            } else {
+                (
+                    quote! {
+                        #read_mtime
+                        #read_bytes
+                        let payload = data.as_slice();
+                        let result: #cached_type = #decode_payload;
+                    },
+                    read_mtime,
+                    quote! { Ok::<Vec<u8>, #error>(#encode_payload) },
+                )
+            }
+        },
+    };
+    let write_code = if compressed {
+        quote! { #serialize.and_then(|data| #runtime_compression.compress(#min_bytes, data).map_err(#error::Io)) }
+    } else {
+        serialize
+    };
+    // write to a sibling temp file and rename it over the cache file, so that a crash mid-write never leaves a
+    // truncated cache file behind
+    let mut write_file = io.write_atomic();
+    let mut remove_entry = quote! { #fs::remove_file(&cache_path)#dot_await };
+    if let Backend::Sled(_) = backend {
+        write_file = quote! { disk_cache::backend::sled_insert(&db_root, &cache_path, &data) };
+        remove_entry = quote! { disk_cache::backend::sled_remove(&db_root, &cache_path) };
+    }
+    // the server drops entries once they expired, unless stale ones are still served
+    let jitter_seconds = match invalidate_jitter {
+        Some((seconds, _)) => quote! { #seconds },
+        None => quote! { 0 },
+    };
+    let redis_ttl = if stale_while_revalidate || never_expires.is_some() {
+        quote! { 0 }
+    } else {
+        quote! { #max_invalidate_rate + #jitter_seconds }
+    };
+    if let Backend::Redis(_) = backend {
+        write_file = quote! { disk_cache::backend::redis_insert(redis_url, &cache_path, &data, #redis_ttl).await };
+        remove_entry = quote! { disk_cache::backend::redis_remove(redis_url, &cache_path).await };
+    }
+    // with the tiered backend, both tiers hold the same bytes, so an entry missing from disk is copied from the server
+    let mut remote_fetch = quote! {};
+    if let Backend::Tiered(_) = backend {
+        write_file = quote! {
+            match #write_file {
+                Ok(()) => disk_cache::backend::redis_insert(redis_url, &cache_path, &data, #redis_ttl).await,
+                Err(e) => Err(e),
+            }
+        };
+        remove_entry = quote! {
+            {
+                let remote = disk_cache::backend::redis_remove(redis_url, &cache_path).await;
+                match #remove_entry {
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => remote,
+                    removed => removed.and(remote),
+                }
+            }
+        };
+        remote_fetch = quote! { disk_cache::backend::redis_fetch(redis_url, &cache_path).await?; };
+    }
+    // with `integrity`, the checksum covers everything written, header included
+    if let Some(checksum) = &integrity {
+        write_file = quote! {
+            {
+                let data = disk_cache::integrity::with_footer::<#checksum>(&data);
+                #write_file
+            }
+        };
+    }
+    // with `format_version`, the header goes in front of what is written, after encryption
+    if let Some(version) = format_version {
+        write_file = quote! {
+            {
+                let data = disk_cache::file::with_header(#version, &data);
+                #write_file
+            }
+        };
+    }
+    // a missing key makes the write fail like any other
+    let encrypt_then_write = if encrypt {
+        quote! {
+            match disk_cache::crypto::encrypt(#encrypt_key_env, &data) {
+                Ok(data) => #write_file,
+                Err(e) => Err(e),
+            }
+        }
+    } else {
+        write_file
+    };
+    let function_name = func_name.to_string();
+    // the state shared by the generated functions, such as the memory tier, is registered under an id naming the
+    // function and where it is defined, as methods of different types may share a name
+    let shared_id = quote_spanned! { func_name.span() =>
+        concat!(module_path!(), "::", #function_name, "@", line!(), ":", column!())
+    };
+    let trace_hit = trace_event(quote! { debug }, quote! {
+        function = #function_name, path = %hit.path, age_ms = hit.age.num_milliseconds(), source = hit.source.as_str(), "cache hit"
+    });
+    let trace_miss = trace_event(quote! { debug }, quote! { function = #function_name, path = %cache_path, bypass, "cache miss" });
+    let trace_written = trace_event(quote! { debug }, quote! { function = #function_name, path = %cache_path, "cache write completed" });
+    let trace_corrupt = trace_event(quote! { warn }, quote! {
+        function = #function_name, path = %cache_path, error = %e, "corrupt cache entry discarded"
+    });
+    // with `on_write_error`, failed writes are handed to the user's function instead of being printed
+    let mut report_write_failure = match &on_write_error {
+        Some(hook) => quote! { #hook(e); },
+        None if cfg!(feature = "tracing") => quote! {},
+        None => quote! { eprintln!("failed to write cache file {}: {}", cache_path, e); },
+    };
+    if cfg!(feature = "tracing") {
+        report_write_failure = quote! {
+            disk_cache::tracing::warn!(function = #function_name, path = %cache_path, error = %e, "cache write failed");
+            #report_write_failure
+        };
+    }
+    // with the `metrics` feature, every call also updates the counters of `disk_cache::stats`
+    let mut record_hit = quote! {};
+    let mut record_miss = quote! {};
+    if cfg!(feature = "metrics") {
+        record_hit = quote! { disk_cache::stats::record_hit(); };
+        record_miss = quote! { disk_cache::stats::record_miss(); };
+        report_write_failure = quote! {
+            #report_write_failure
+            disk_cache::stats::record_write_error();
+        };
+    }
+    // with `on_event`, the same steps are reported to the user's function
+    if let Some(hook) = &on_event {
+        record_hit = quote! {
+            #record_hit
+            #hook(disk_cache::CacheEvent::Hit { age: hit.age.to_std().unwrap_or_default() });
+        };
+        record_miss = quote! {
+            #record_miss
+            #hook(disk_cache::CacheEvent::Miss);
+        };
+        report_write_failure = quote! {
+            #hook(disk_cache::CacheEvent::WriteErr(&e));
+            #report_write_failure
+        };
+    }
+    let report_write_success = match &on_event {
+        Some(hook) => quote! { #hook(disk_cache::CacheEvent::WriteOk); },
+        None => quote! {},
+    };
+
+    // with `max_entries`, the least recently modified entries beyond the limit are removed after each write
+    let mut prune_steps = Vec::new();
+    let mut prune_captures = Vec::new();
+    if let Some((max_entries, _)) = max_entries {
+        prune_captures.push(quote! { let entry_dir = entry_dir.clone(); });
+        prune_steps.push(quote! { disk_cache::prune::entries(&entry_dir, #max_entries); });
+    }
+    // with `max_bytes`, the oldest cache files under the static part of the cache root are removed until the total fits
+    if let Some((max_bytes, _)) = max_bytes {
+        // named cache files cannot be told apart from other files by their name
+        let any_name = file_name_lit.is_some();
+        prune_captures.push(quote! { let budget_root = budget_root.clone(); });
+        prune_steps.push(quote! { disk_cache::prune::bytes(&budget_root, #max_bytes, #any_name); });
+    }
+    let mut prune = quote! {};
+    if !prune_steps.is_empty() {
+        let task = io.spawn_blocking(quote! { #(#prune_steps)* });
+        prune = quote! {
+            #(#prune_captures)*
+            #task
+        };
+    }
+    let write_task = quote! {
+        static WRITE_COUNTER: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+        let write_id = WRITE_COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        let tmp_path = format!("{}.tmp.{}.{}", cache_path, std::process::id(), write_id);
+        let written = #encrypt_then_write;
+        match written {
+            Ok(()) => {
+                #trace_written
+                #report_write_success
+                #prune
+            }
+            Err(e) => {
+                #report_write_failure
+            }
+        }
+    };
+    // the write must land before the in-flight lock is released, otherwise the waiters would miss the cache
+    let write = if blocking_write || single_flight || lock.is_some() {
+        quote! { { #write_task } }
+    } else {
+        io.spawn(&spawn, write_task.clone())
+    };
+
+    // with `single_flight`, a per-path lock is held from the validity check until the cache file is written. The locks
+    // are shared by the function, `_prime` and `_with_meta`, so that a call through any of them waits for the others
+    let mut in_flight = quote! {};
+    if single_flight {
+        let mutex = io.mutex();
+        let lock = io.lock(quote! { key_lock });
+        in_flight = quote! {
+            static IN_FLIGHT: std::sync::OnceLock<&'static std::sync::Mutex<std::collections::HashMap<String, std::sync::Arc<#mutex<()>>>>> = std::sync::OnceLock::new();
+            let key_lock = IN_FLIGHT
+                .get_or_init(|| disk_cache::shared::get(#shared_id, Default::default))
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .entry(cache_path.clone())
+                .or_default()
+                .clone();
+            let _in_flight = #lock;
+        };
+    }
+
+    // how errors of the cache layer itself are surfaced; `e` is the error
+    let handle_error = match on_error {
+        OnError::Propagate => quote! { return Err(e); },
+        OnError::Panic => quote! { panic!("cache error for {}: {}", cache_path, e); },
+        OnError::Fallback => quote! { eprintln!("cache error for {}: {}", cache_path, e); },
+        OnError::Convert => quote! { return Err(<#convert_type>::from(std::io::Error::from(e))); },
+    };
+    let mut write_result = quote! {
+        match #write_code {
+            Ok(data) => { #write }
+            Err(e) => { #handle_error }
+        }
+    };
+    // the write always lands before returning, so that the next call is a hit
+    let mut prime_write = quote! {
+        let data = #write_code?;
+        #write_task
+    };
+    // with `read_only`, nothing is ever written
+    if read_only.is_some() {
+        write_result = quote! {};
+        prime_write = quote! { let _ = result; };
+    }
+
+    let final_return = wrap_return(return_call.clone());
+    // with `cache_if`, a computed value is only stored, in memory or on disk, when the predicate accepts it, and with
+    // `min_compute_time_ms` when computing it took long enough
+    let mut conditions = Vec::new();
+    if let Some(predicate) = &cache_if {
+        conditions.push(quote! { #predicate(&result) });
+    }
+    if let Some(millis) = min_compute_time_ms {
+        conditions.push(quote! { compute_started.elapsed() >= std::time::Duration::from_millis(#millis) });
+    }
+    let only_if_cacheable = |store: proc_macro2::TokenStream| {
+        if conditions.is_empty() {
+            store
+        } else {
+            quote! {
+                if #(#conditions)&&* {
+                    #store
+                }
+            }
+        }
+    };
+    // how the entries read are judged: by their expiry, with `validate` a rejected value is a miss whatever its age,
+    // with `refresh_ahead` a hit about to expire is refreshed as well, and with `stale_while_revalidate` an expired
+    // entry is still served, and refreshed
+    let expiry_binding = if cache_errors.is_some() { quote! { result } } else { quote! { _ } };
+    let validate_policy = match &validate {
+        Some(validate) => quote! { Some(&|result: &#cached_type| #validate(result)) },
+        None => quote! { None },
+    };
+    let refresh_ahead_policy = match refresh_ahead {
+        Some((seconds, _)) => quote! { Some(chrono::Duration::seconds(#seconds)) },
+        None => quote! { None },
+    };
+    let policy = quote! {
+        let policy = disk_cache::lookup::Policy {
+            expiry: &|#expiry_binding: &#cached_type| #entry_expiry,
+            validate: #validate_policy,
+            refresh_ahead: #refresh_ahead_policy,
+            serve_stale: #stale_while_revalidate,
+        };
+    };
+    // with `memory_capacity`, entries are kept in a process-global LRU keyed by cache path in front of the disk, shared
+    // with `clear_<name>` and `<name>_clear_all`, which evict from it too
+    let mut memory_static = quote! {};
+    let mut memory_get = quote! {};
+    let mut memory_insert_read = quote! {};
+    let mut memory_insert_computed = quote! {};
+    if let Some(capacity) = memory_capacity {
+        memory_static = quote! {
+            static MEMORY: std::sync::OnceLock<&'static disk_cache::lookup::Memory<#cached_type>> = std::sync::OnceLock::new();
+            let memory = *MEMORY.get_or_init(|| disk_cache::shared::get(#shared_id, || disk_cache::lookup::Memory::new(#capacity)));
+        };
+        memory_get = quote! {
+            if let Some(hit) = memory.get(&cache_path, &policy) {
+                return Ok(Some(hit));
+            }
+        };
+        memory_insert_read = quote! { memory.insert(cache_path.clone(), hit.last_written, hit.value.clone()); };
+        memory_insert_computed = quote! { memory.insert(cache_path.clone(), disk_cache::clock::now(), result.clone()); };
+    }
+    // with `stale_while_revalidate` or `refresh_ahead`, a hit may be computed again by a background task
+    let mut refresh_spawn = quote! {};
+    if stale_while_revalidate || refresh_ahead.is_some() {
+        let unwrap_ok = if is_result {
+            quote! {
+                let result = match result {
+                    Ok(result) => result,
+                    Err(_) => return,
+                };
+            }
+        } else {
+            quote! {}
+        };
+        let refresh_store = only_if_cacheable(quote! {
+            #memory_insert_computed
+            match #write_code {
+                Ok(data) => { #write_task }
+                Err(e) => eprintln!("cache error for {}: {}", cache_path, e),
+            }
+        });
+        let refresh = io.spawn(&spawn, quote! {
+            #start_timer
+            let result: #func_type = #body_call;
+            #unwrap_ok
+            #refresh_store
+        });
+        refresh_spawn = quote! {
+            if hit.refresh {
+                #refresh
+            }
+        };
+    }
+    // with `touch_on_hit`, a hit read from disk refreshes the mtime that `max_entries` and `max_bytes` evict by
+    let mut touch = quote! {};
+    if touch_on_hit.is_some() {
+        let (entry_dir_capture, entry_dir) = match max_entries {
+            Some(_) => (quote! { let entry_dir = entry_dir.clone(); }, quote! { Some(entry_dir.as_str()) }),
+            None => (quote! {}, quote! { None }),
+        };
+        let task = io.spawn_blocking(quote! { disk_cache::file::touch(&cache_path, #entry_dir); });
+        touch = quote! {
+            {
+                let cache_path = hit.path.clone();
+                #entry_dir_capture
+                #task
+            }
+        };
+    }
+    // an entry read from disk, rather than from memory, and still fresh
+    let mut disk_hit = quote! {};
+    if memory_capacity.is_some() || touch_on_hit.is_some() {
+        disk_hit = quote! {
+            if hit.source == disk_cache::lookup::Source::Disk {
+                #memory_insert_read
+                #touch
+            }
+        };
+    }
+    let take_hit = quote! {
+        #trace_hit
+        #disk_hit
+        #refresh_spawn
+    };
+    // with `read_fallback`, the same entry below the fallback root is read when the one below the cache root is missing
+    // or expired. It is never written nor removed
+    let mut read_paths = quote! { [cache_path.clone()] };
+    let mut fallback_path = quote! {};
+    if let Some(fallback_root) = &fallback_root {
+        fallback_path = quote! {
+            let read_root: String = #fixed_root;
+            let fallback_path: String = format!("{}{}", #fallback_root, cache_path.strip_prefix(read_root.as_str()).unwrap_or_default());
+        };
+        read_paths = quote! { [cache_path.clone(), fallback_path] };
+    }
+    // with `format = "auto"`, the file of the written format is read first, then those of the other formats
+    if !auto_file_names.is_empty() {
+        read_paths = quote! {
+            #read_paths.into_iter().flat_map(|cache_path| {
+                let entry_stem: String = cache_path.strip_suffix(#file_name).unwrap_or_default().to_string();
+                [#(format!("{}{}", entry_stem, #auto_file_names)),*]
+            })
+        };
+    }
+    // the entries are read by a closure, which the lookup calls with each of the paths
+    let read_one = quote! {
+        if !#exists? {
+            return Ok(None);
+        }
+        #read_entry
+        Ok::<_, #error>(Some((last_written, result)))
+    };
+    let disk_lookup = match io {
+        Io::Tokio | Io::AsyncStd => {
+            // the future of each read borrows what it needs from the generated function
+            let mut read_captures = quote! {};
+            if let Backend::Sled(_) = backend {
+                read_captures = quote! { let db_root = &db_root; };
+            }
+            if encrypt {
+                read_captures = quote! {
+                    #read_captures
+                    let key = &key;
+                };
+            }
+            quote! {
+                disk_cache::lookup::lookup_async(#read_paths, &policy, |cache_path: String| {
+                    #read_captures
+                    async move {
+                        #read_one
+                    }
+                })
+                .await
+            }
+        },
+        Io::Std => quote! {
+            disk_cache::lookup::lookup(#read_paths, &policy, |cache_path: String| {
+                #read_one
+            })
+        },
+    };
+    let lookup = io.try_block(quote! {
+        #policy
+        #memory_get
+        #load_key
+        #remote_fetch
+        #fallback_path
+        #disk_lookup
+    });
+    // an entry that cannot be decoded is a miss, and is removed so that it gets rewritten
+    let remove_corrupt = if read_only.is_some() { quote! {} } else { quote! { let _ = #remove_entry; } };
+    let lookup = quote! {
+        match #lookup {
+            #[allow(unused_variables)]
+            Err(#error::Deserialize(e)) => {
+                #trace_corrupt
+                #remove_corrupt
+                Ok(None)
+            }
+            cached => cached,
+        }
+    };
+    // Check if the cache is still valid, and read it if so. When bypassed, the function always runs
+    let cached_type_hit = quote! { Result<Option<disk_cache::lookup::Hit<#cached_type>>, #error> };
+    let mut find_cached = quote! {
+        let cached: #cached_type_hit = if bypass { Ok(None) } else { #lookup };
+    };
+    // with `lock`, a miss takes the lock of the entry across processes, and looks the entry up again once it holds it, in
+    // case another process wrote it meanwhile. The lock is held until the entry is written
+    if lock.is_some() {
+        let acquire = match io {
+            Io::Tokio => quote! { disk_cache::lock::acquire_async(&cache_path).await },
+            Io::AsyncStd => quote! {
+                {
+                    let cache_path = cache_path.clone();
+                    disk_cache::async_std::task::spawn_blocking(move || disk_cache::lock::acquire(&cache_path)).await
+                }
+            },
+            Io::Std => quote! { disk_cache::lock::acquire(&cache_path) },
+        };
+        find_cached = quote! {
+            let mut entry_lock = None;
+            let cached: #cached_type_hit = loop {
+                let cached = if bypass { Ok(None) } else { #lookup };
+                match cached {
+                    Ok(None) if entry_lock.is_none() => match #acquire {
+                        Ok(acquired) => entry_lock = Some(acquired),
+                        Err(e) => break Err(e.into()),
+                    },
+                    cached => break cached,
+                }
+            };
+        };
+    }
+    find_cached = quote! {
+        let bypass = std::env::var(#bypass_env)
+            .map(|value| !value.is_empty() && value != "0" && !value.eq_ignore_ascii_case("false"))
+            .unwrap_or(false);
+        #find_cached
+    };
+
+    let clear_name = format_ident!("clear_{}", func_name);
+    let prime_name = format_ident!("{}_prime", func_name);
+    // an `Err` of the function is never cached, so priming just discards it
+    let prime_unwrap = if is_result {
+        quote! {
+            let result = match result {
+                Ok(result) => result,
+                Err(_) => return Ok(()),
+            };
+        }
+    } else {
+        quote! {}
+    };
+    let store = only_if_cacheable(quote! {
+        #memory_insert_computed
+        #write_result
+    });
+    let prime_store = only_if_cacheable(quote! {
+        #memory_insert_computed
+        #prime_write
+    });
+    // `_with_meta` returns the errors of the cache layer, like `_prime`, and returns an `Err` of the function as it is
+    let mut meta_write = quote! {
+        match #write_code {
+            Ok(data) => { #write }
+            Err(e) => return Err(e),
+        }
+    };
+    if read_only.is_some() {
+        meta_write = quote! {};
+    }
+    let meta_store = only_if_cacheable(quote! {
+        #memory_insert_computed
+        #meta_write
+    });
+    let meta_unwrap = if is_result {
+        quote! {
+            let result = match result {
+                Ok(result) => result,
+                Err(e) => return Ok((Err(e), meta)),
+            };
+        }
+    } else {
+        quote! {}
+    };
+    let with_meta_name = format_ident!("{}_with_meta", func_name);
+    let is_cached_name = format_ident!("{}_is_cached", func_name);
+    let clear_all_name = format_ident!("{}_clear_all", func_name);
+    let cache_path_name = format_ident!("{}_cache_path", func_name);
+    // computing the path never touches the filesystem, so it is a plain function even for `cache_async`
+    let cache_path_fn = quote! {
+        /// Returns where the corresponding cached function reads and writes its entry for the given arguments. With the
+        /// `sled` and `redis` backends, this is the key of the entry.
+        #[allow(unused_variables)]
+        #func_vis fn #cache_path_name #func_generics(#func_args) -> std::path::PathBuf #user_where_clause {
+            #key_code
+            std::path::PathBuf::from(format!("{}/{}", cache_dir, #file_name))
+        }
+    };
+    // `_clear_all` removes the whole tree below the fixed directories of this function, as relocated by `cache_root_env`
+    // or `config`
+    let clear_root = match &relocated_root {
+        Some(relocated_root) if function_rest.is_empty() => quote! {
+            #relocated_root.map(|root| root.trim_end_matches('/').to_string()).unwrap_or_else(|| #function_prefix.to_string())
+        },
+        Some(relocated_root) => quote! {
+            #relocated_root.map(|root| format!("{}/{}", root.trim_end_matches('/'), #function_rest)).unwrap_or_else(|| #function_prefix.to_string())
+        },
+        None => quote! { #function_prefix.to_string() },
+    };
+    let remove_dir = quote! {
+        match #fs::remove_dir_all(&clear_root)#dot_await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {},
+        }
+    };
+    // entries stored outside files are keyed by their path, so those below the directory are removed by prefix
+    let remove_all = match backend {
+        Backend::Files => remove_dir,
+        Backend::Sled(_) => quote! {
+            let db_root: String = #fixed_root;
+            disk_cache::backend::sled_remove_prefix(&db_root, &format!("{}/", clear_root))?;
+        },
+        Backend::Redis(_) => quote! {
+            disk_cache::backend::redis_remove_prefix(#redis_url, &format!("{}/", clear_root)).await?;
+        },
+        Backend::Tiered(_) => quote! {
+            disk_cache::backend::redis_remove_prefix(#redis_url, &format!("{}/", clear_root)).await?;
+            #remove_dir
+        },
+    };
+    // with `memory_capacity`, the entries are forgotten by the memory tier as well, and looked up there by `_is_cached`
+    let mut memory_remove = quote! {};
+    let mut memory_clear = quote! {};
+    let mut memory_is_cached = quote! {};
+    if memory_capacity.is_some() {
+        memory_remove = quote! {
+            #memory_static
+            memory.remove(&cache_path);
+        };
+        memory_clear = quote! {
+            #memory_static
+            memory.clear();
+        };
+        // a fresh value in memory makes the next call a hit as well, whatever is on disk
+        memory_is_cached = quote! {
+            #memory_static
+            #policy
+            if memory.get(&cache_path, &policy).is_some() {
+                return Ok(true);
+            }
+        };
+    }
+    // without any fixed directory of its own, the function's entries cannot be told apart from anything else
+    let clear_all_body = if function_prefix.trim_matches('/').trim_matches('.').is_empty() {
+        quote! {
+            Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "`cache_root` has no fixed directories to clear").into())
+        }
+    } else {
+        quote! {
+            let clear_root: String = #clear_root;
+            #remove_all
+            Ok(())
+        }
+    };
+    // the validity check of `_is_cached` only reads the write time, unless the expiry depends on the payload
+    let is_cached_check = if cache_errors.is_some() {
+        quote! {
+            #read_entry
+            Ok(disk_cache::clock::now().signed_duration_since(last_written) < #entry_expiry)
+        }
+    } else {
+        quote! {
+            #read_stamp
+            Ok(disk_cache::clock::now().signed_duration_since(last_written) < #value_expiry)
+        }
+    };
+    let is_cached_check = io.try_block(is_cached_check);
+    // with several files to look at, any valid one will do, and a corrupt one is not a valid one
+    let is_cached_body = quote! {
+        #memory_is_cached
+        #remote_fetch
+        #load_key_is_cached
+        #fallback_path
+        for cache_path in #read_paths {
+            if !#exists? {
+                continue;
+            }
+            match #is_cached_check {
+                Ok(true) => return Ok(true),
+                Ok(false) | Err(#error::Deserialize(_)) => {},
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(false)
+    };
+    // with the `disabled` feature, the signatures stay the same but nothing touches the filesystem
+    if cfg!(feature = "disabled") {
+        return quote! {
+            #[allow(unused_variables)]
+            #must_use
+            #func_vis #asyncness fn #func_name #func_generics(#func_args) -> #return_type #where_clause {
+                #compute
+                #calling_code
+                #final_return
+            }
+
+            /// Does nothing, as caching is disabled.
+            #[allow(unused_variables)]
+            #func_vis #asyncness fn #clear_name #func_generics(#func_args) -> Result<(), #error> #user_where_clause {
+                Ok(())
+            }
+
+            /// Does nothing, as caching is disabled.
+            #[allow(unused_variables)]
+            #func_vis #asyncness fn #prime_name #func_generics(#func_args) -> Result<(), #error> #where_clause {
+                Ok(())
+            }
+
+            /// Runs the corresponding function, as caching is disabled.
+            #[allow(unused_variables)]
+            #func_vis #asyncness fn #with_meta_name #func_generics(#func_args) -> Result<(#surface_type, disk_cache::CacheMeta), #error> #where_clause {
+                #key_code
+                let path = std::path::PathBuf::from(format!("{}/{}", cache_dir, #file_name));
+                #compute
+                let result: #func_type = #body_call;
+                Ok((#whole_return, disk_cache::CacheMeta { hit: false, age: std::time::Duration::ZERO, path }))
+            }
+
+            /// Does nothing, as caching is disabled.
+            #func_vis #asyncness fn #clear_all_name() -> Result<(), #error> {
+                Ok(())
+            }
+
+            /// Always returns `false`, as caching is disabled.
+            #[allow(unused_variables)]
+            #func_vis #asyncness fn #is_cached_name #func_generics(#func_args) -> Result<bool, #error> #user_where_clause {
+                Ok(false)
+            }
+
+            #cache_path_fn
+        }
+        .into();
+    }
+    let output = quote! {
+        #must_use
+        #func_vis #asyncness fn #func_name #func_generics(#func_args) -> #return_type #where_clause {
+            #key_code
+            // now we have the cache path. put the data file at the end
+            let cache_path: String = format!("{}/{}", cache_dir, #file_name);
+            #memory_static
+            #in_flight
+            #find_cached
+            #compute
+            match cached {
+                Ok(Some(hit)) => {
+                    #record_hit
+                    #take_hit
+                    let result = hit.value;
+                    return #final_return;
+                },
+                Ok(None) => {
+                    #record_miss
+                    #trace_miss
+                },
+                // an entry that cannot be read counts as a miss, whether the body then runs or not
+                Err(e) => {
+                    #record_miss
+                    #trace_miss
+                    #handle_error
+                }
+            }
+            // Get the data from the function
+            #calling_code
+            // Write the data to the cache, either in a spawned task or before returning
+            #store
+            #final_return
+        } 
+
+        /// Removes the cache entry of the corresponding cached function for the given arguments, so that the next call
+        /// runs the function again.
+        #[allow(unused_variables)]
+        #func_vis #asyncness fn #clear_name #func_generics(#func_args) -> Result<(), #error> #user_where_clause {
+            #key_code
+            let cache_path: String = format!("{}/{}", cache_dir, #file_name);
+            #memory_remove
+            match #remove_entry {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+                _ => Ok(()),
+            }
+        }
+
+        /// Runs the corresponding cached function for the given arguments and writes its result to the cache, unless a
+        /// valid entry already exists. The value itself is discarded.
+        #func_vis #asyncness fn #prime_name #func_generics(#func_args) -> Result<(), #error> #where_clause {
+            #key_code
+            let cache_path: String = format!("{}/{}", cache_dir, #file_name);
+            #memory_static
+            #in_flight
+            #find_cached
+            #compute
+            if let Some(hit) = cached? {
+                #take_hit
+                return Ok(());
+            }
+            #start_timer
+            let result: #func_type = #body_call;
+            #prime_unwrap
+            #prime_store
+            Ok(())
+        }
+
+        /// Runs the corresponding cached function for the given arguments, and returns its value with whether it was read
+        /// from the cache, how old it is and where it is stored.
+        #func_vis #asyncness fn #with_meta_name #func_generics(#func_args) -> Result<(#surface_type, disk_cache::CacheMeta), #error> #where_clause {
+            #key_code
+            let cache_path: String = format!("{}/{}", cache_dir, #file_name);
+            #memory_static
+            #in_flight
+            #find_cached
+            #compute
+            let cached = match cached {
+                Ok(cached) => cached,
+                Err(e) => {
+                    #record_miss
+                    #trace_miss
+                    return Err(e);
+                }
+            };
+            let path = std::path::PathBuf::from(&cache_path);
+            if let Some(hit) = cached {
+                #record_hit
+                let age = hit.age.to_std().unwrap_or_default();
+                #take_hit
+                let result = hit.value;
+                let meta = disk_cache::CacheMeta { hit: true, age, path };
+                return Ok((#return_call, meta));
+            }
+            #record_miss
+            #trace_miss
+            #start_timer
+            let result: #func_type = #body_call;
+            let meta = disk_cache::CacheMeta { hit: false, age: std::time::Duration::ZERO, path };
+            #meta_unwrap
+            #meta_store
+            Ok((#return_call, meta))
+        }
+
+        /// Removes every cache entry of the corresponding cached function, for all arguments, along with the directories
+        /// holding them.
+        #func_vis #asyncness fn #clear_all_name() -> Result<(), #error> {
+            #memory_clear
+            #clear_all_body
+        }
+
+        /// Returns whether the corresponding cached function has a valid cache entry for the given arguments, without
+        /// running it.
+        #[allow(unused_variables)]
+        #func_vis #asyncness fn #is_cached_name #func_generics(#func_args) -> Result<bool, #error> #user_where_clause {
+            #key_code
+            let cache_path: String = format!("{}/{}", cache_dir, #file_name);
+            #is_cached_body
+        }
+
+        #cache_path_fn
+    };
+
+    output.into()
+
+}
+
+/// The arguments of the cache attributes: `name = literal` pairs, plus `name = path` pairs for attributes naming a
+/// function.
+struct CacheArgs {
+    literals: Vec<NestedMeta>,
+    paths: Vec<(syn::Ident, syn::Path)>,
+}
+
+impl Parse for CacheArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut literals = Vec::new();
+        let mut paths = Vec::new();
+        while !input.is_empty() {
+            if input.peek(syn::Ident) && input.peek2(syn::Token![=]) && !input.peek3(Lit) {
+                let name: syn::Ident = input.parse()?;
+                input.parse::<syn::Token![=]>()?;
+                paths.push((name, input.parse()?));
+            } else {
+                literals.push(input.parse()?);
+            }
+            if input.is_empty() {
+                break;
+            }
+            input.parse::<syn::Token![,]>()?;
+        }
+        Ok(CacheArgs { literals, paths })
+    }
+}
+
+/// The serialization backend used for the cache file.
+enum Format {
+    Json,
+    Bincode,
+    Cbor,
+    Ron,
+    MessagePack,
+    Toml,
+    Yaml,
+    /// Written as JSON, but read in whichever of the formats above the extension of the file names.
+    Auto,
+    /// The user's own pair of functions, `fn(&T) -> Vec<u8>` and `fn(&[u8]) -> Result<T, E>`.
+    Custom {
+        serialize: syn::Path,
+        deserialize: syn::Path,
+    },
+    /// The bytes of a `Vec<u8>`, as they are.
+    Raw,
+}
+
+impl Format {
+    /// The formats going through serde, in the order `format = "auto"` looks for their files.
+    const SERDE: [Format; 7] = [
+        Format::Json,
+        Format::Bincode,
+        Format::Cbor,
+        Format::Ron,
+        Format::MessagePack,
+        Format::Toml,
+        Format::Yaml,
+    ];
+
+    /// Extension of the data file inside the cache directory. Each format gets its own extension so that
+    /// switching formats never reads a stale file written by another backend.
+    fn extension(&self) -> &'static str {
+        match self {
+            Format::Json | Format::Auto => "json",
+            Format::Bincode => "bin",
+            Format::Cbor => "cbor",
+            Format::Ron => "ron",
+            Format::MessagePack => "msgpack",
+            Format::Toml => "toml",
+            Format::Yaml => "yaml",
+            Format::Custom { .. } => "custom",
+            Format::Raw => "raw",
+        }
+    }
+
+    /// The `disk_cache::codec::Format` entries are read and written in, which `format = "auto"` writes them in. The
+    /// custom and raw encodings go through no serde format.
+    fn runtime(&self) -> Option<proc_macro2::TokenStream> {
+        match self {
+            Format::Json | Format::Auto => Some(quote! { disk_cache::codec::Format::Json }),
+            Format::Bincode => Some(quote! { disk_cache::codec::Format::Bincode }),
+            Format::Cbor => Some(quote! { disk_cache::codec::Format::Cbor }),
+            Format::Ron => Some(quote! { disk_cache::codec::Format::Ron }),
+            Format::MessagePack => Some(quote! { disk_cache::codec::Format::MessagePack }),
+            Format::Toml => Some(quote! { disk_cache::codec::Format::Toml }),
+            Format::Yaml => Some(quote! { disk_cache::codec::Format::Yaml }),
+            Format::Custom { .. } | Format::Raw => None,
+        }
+    }
+}
+
+/// Where the serialized entries are stored.
+enum Backend<'a> {
+    /// One file per entry.
+    Files,
+    /// One embedded `sled` database per cache root.
+    Sled(&'a syn::LitStr),
+    /// A Redis server, shared by every process using it.
+    Redis(&'a syn::LitStr),
+    /// One file per entry, in front of a Redis server holding the same entries.
+    Tiered(&'a syn::LitStr),
+}
+
+/// The compression applied to the serialized bytes, independently of the format.
+enum Compression {
+    None,
+    /// With its level, from 0 to 9.
+    Gzip(u32),
+    /// With its level, from 1 to 22.
+    Zstd(i32),
+}
+
+impl Compression {
+    /// Suffix appended to the data file name.
+    fn extension(&self) -> &'static str {
+        match self {
+            Compression::None => "",
+            Compression::Gzip(_) => ".gz",
+            Compression::Zstd(_) => ".zst",
+        }
+    }
+
+    /// The `disk_cache::codec::Compression` of the entries.
+    fn runtime(&self) -> proc_macro2::TokenStream {
+        match self {
+            Compression::None => quote! { disk_cache::codec::Compression::None },
+            Compression::Gzip(level) => quote! { disk_cache::codec::Compression::Gzip(#level) },
+            Compression::Zstd(level) => quote! { disk_cache::codec::Compression::Zstd(#level) },
+        }
+    }
+}
+
+/// How errors of the cache layer are surfaced to the caller.
+enum OnError {
+    Propagate,
+    Panic,
+    Fallback,
+    /// Converted into the error type of the function, with `cache_fallible`.
+    Convert,
+}
+
+/// How the background tasks writing and refreshing entries are run.
+enum Spawn {
+    /// Detached onto the runtime, or a thread for `cache`, so they must be `Send`.
+    Detached,
+    /// With `tokio::task::spawn_local`, on the `LocalSet` the function is called from.
+    Local,
+    /// Run to completion before the function returns.
+    Inline,
+}
+
+/// The IO flavor of the generated code: `tokio` (or `async-std` with the feature of the same name) for `cache_async`,
+/// `std` for `cache`.
+enum Io {
+    Tokio,
+    AsyncStd,
+    Std,
+}
+
+impl Io {
+    /// Path of the filesystem module.
+    fn fs(&self) -> proc_macro2::TokenStream {
+        match self {
+            Io::Tokio => quote! { tokio::fs },
+            Io::AsyncStd => quote! { disk_cache::async_std::fs },
+            Io::Std => quote! { std::fs },
+        }
+    }
+
+    fn asyncness(&self) -> proc_macro2::TokenStream {
+        match self {
+            Io::Tokio | Io::AsyncStd => quote! { async },
+            Io::Std => quote! {},
+        }
+    }
+
+    /// Suffix applied to every filesystem call.
+    fn dot_await(&self) -> proc_macro2::TokenStream {
+        match self {
+            Io::Tokio | Io::AsyncStd => quote! { .await },
+            Io::Std => quote! {},
+        }
+    }
+
+    /// Expression checking whether the file at `path` exists, as a `Result<bool, _>`.
+    fn exists(&self, path: proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+        match self {
+            Io::Tokio => quote! { tokio::fs::try_exists(#path).await },
+            Io::AsyncStd => quote! { Ok::<bool, std::io::Error>(disk_cache::async_std::path::Path::new(#path).exists().await) },
+            Io::Std => quote! { std::fs::exists(#path) },
+        }
+    }
+
+    /// Closure running the original function body, so that a `return` inside it doesn't return from the generated
+    /// function. With `output`, the type the body must evaluate to, the body is checked against it, and the closure
+    /// carries the body's span, so that errors point at the user's code.
+    fn compute(&self, body: &syn::Block, output: Option<&Type>) -> proc_macro2::TokenStream {
+        match (self, output) {
+            (Io::Tokio | Io::AsyncStd, Some(output)) => quote_spanned! { body.span() =>
+                move || async move {
+                    let output: #output = #body;
+                    output
+                }
+            },
+            (Io::Tokio | Io::AsyncStd, None) => quote_spanned! { body.span() => move || async move #body },
+            (Io::Std, Some(output)) => quote_spanned! { body.span() => move || -> #output #body },
+            (Io::Std, None) => quote_spanned! { body.span() => move || #body },
+        }
+    }
+
+    /// Expression evaluating `block`, in which `?` and `return` apply to the block rather than to the generated function.
+    fn try_block(&self, block: proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+        match self {
+            Io::Tokio | Io::AsyncStd => quote! { async { #block }.await },
+            Io::Std => quote! { disk_cache::block::run(|| { #block }) },
+        }
+    }
+
+    /// Expression writing `data` to `tmp_path` and renaming it over `cache_path`.
+    fn write_atomic(&self) -> proc_macro2::TokenStream {
+        match self {
+            Io::Tokio => quote! { disk_cache::file::write_atomic_async(&cache_path, &tmp_path, &data).await },
+            Io::AsyncStd => quote! {
+                {
+                    let (cache_path, tmp_path) = (cache_path.clone(), tmp_path.clone());
+                    disk_cache::async_std::task::spawn_blocking(move || disk_cache::file::write_atomic(&cache_path, &tmp_path, &data)).await
+                }
+            },
+            Io::Std => quote! { disk_cache::file::write_atomic(&cache_path, &tmp_path, &data) },
+        }
+    }
+
+    /// Mutex type that can be held while the cache is checked and written.
+    fn mutex(&self) -> proc_macro2::TokenStream {
+        match self {
+            Io::Tokio => quote! { tokio::sync::Mutex },
+            Io::AsyncStd => quote! { disk_cache::async_std::sync::Mutex },
+            Io::Std => quote! { std::sync::Mutex },
+        }
+    }
+
+    /// Expression locking the `mutex` held by `lock`.
+    fn lock(&self, lock: proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+        match self {
+            Io::Tokio | Io::AsyncStd => quote! { #lock.lock().await },
+            Io::Std => quote! { #lock.lock().unwrap_or_else(|e| e.into_inner()) },
+        }
+    }
+
+    /// Detaches `task`, which may block on synchronous filesystem calls, so that it runs in the background.
+    fn spawn_blocking(&self, task: proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+        match self {
+            Io::Tokio => quote! {
+                let _ = tokio::task::spawn_blocking(move || {
+                    #task
+                });
+            },
+            Io::AsyncStd => quote! {
+                let _ = disk_cache::async_std::task::spawn_blocking(move || {
+                    #task
+                });
+            },
+            Io::Std => quote! {
+                let _ = std::thread::spawn(move || {
+                    #task
+                });
+            },
+        }
+    }
+
+    /// Detaches `task` so that it runs in the background, or runs it in place, as `spawn` asks.
+    fn spawn(&self, spawn: &Spawn, task: proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+        match (spawn, self) {
+            // the task is still a block of its own, so that an early `return` in it does not leave the function
+            (Spawn::Inline, Io::Tokio | Io::AsyncStd) => quote! {
+                async move {
+                    #task
+                }
+                .await;
+            },
+            (Spawn::Inline, Io::Std) => quote! {
+                disk_cache::block::run(move || {
+                    #task
+                });
+            },
+            (Spawn::Local, _) => quote! {
+                let _ = tokio::task::spawn_local(async move {
+                    #task
+                });
+            },
+            (Spawn::Detached, Io::Tokio) => quote! {
+                let _ = tokio::spawn(async move {
+                    #task
+                });
+            },
+            (Spawn::Detached, Io::AsyncStd) => quote! {
+                let _ = disk_cache::async_std::task::spawn(async move {
+                    #task
+                });
+            },
+            (Spawn::Detached, Io::Std) => quote! {
+                let _ = std::thread::spawn(move || {
+                    #task
+                });
+            },
+        }
+    }
+}
+
+/// Turns a cache path template such as `"./cache/{arg}"` into a `format!` call interpolating the sanitized value of every
+/// placeholder. Literal braces are written `{{` and `}}`, and `{name:spec}` applies `spec` before sanitizing. Every
+/// placeholder must start from one of `params`, e.g. `{name}`, `{user.id}` or `{query.len()}`.
+fn template_format(template: &str, params: &[String]) -> Result<proc_macro2::TokenStream, String> {
+    let mut format_string = String::new();
+    let mut values = Vec::new();
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                format_string.push_str("{{");
+            },
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                format_string.push_str("}}");
+            },
+            '{' => {
+                let mut placeholder = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => placeholder.push(c),
+                        None => return Err(format!("unclosed placeholder in cache path `{}`", template)),
+                    }
+                }
+                let (name, spec) = placeholder.split_once(':').unwrap_or((&placeholder, ""));
+                let name = name.trim();
+                if name.is_empty() {
+                    return Err(format!("placeholders in cache path `{}` must name an argument, e.g. `{{arg}}`", template));
+                }
+                let invalid = || {
+                    format!(
+                        "invalid placeholder `{{{}}}` in cache path `{}`, expected an argument, a field of one such as `{{user.id}}`, or a method call without arguments on one",
+                        placeholder, template
+                    )
+                };
+                let expr = syn::parse_str::<syn::Expr>(name).map_err(|_| invalid())?;
+                let name = placeholder_root(&expr).ok_or_else(invalid)?.to_string();
+                if !params.contains(&name) {
+                    let expected = params.iter().map(|param| format!("`{}`", param)).collect::<Vec<_>>().join(", ");
+                    return Err(if expected.is_empty() {
+                        format!("placeholder `{{{}}}` in cache path `{}` does not name an argument, the function has none", name, template)
+                    } else {
+                        format!("placeholder `{{{}}}` in cache path `{}` does not name an argument, expected one of {}", name, template, expected)
+                    });
+                }
+                let spec = format!("{{:{}}}", spec);
+                format_string.push_str("{}");
+                values.push(quote! { sanitize_key(format!(#spec, #expr)) });
+            },
+            '}' => return Err(format!("unmatched `}}` in cache path `{}`", template)),
+            c => format_string.push(c),
+        }
+    }
+    Ok(quote! { format!(#format_string, #(#values),*) })
+}
+
+/// The argument a placeholder expression starts from, provided that the rest of it only accesses fields and calls
+/// methods without arguments.
+fn placeholder_root(expr: &syn::Expr) -> Option<&syn::Ident> {
+    match expr {
+        syn::Expr::Path(path) if path.qself.is_none() => path.path.get_ident(),
+        syn::Expr::Field(field) => placeholder_root(&field.base),
+        syn::Expr::MethodCall(call) if call.args.is_empty() && call.turbofish.is_none() => placeholder_root(&call.receiver),
+        _ => None,
+    }
+}
+
+/// A hash of the tokens of `block`, as 16 hex digits. It is FNV-1a, which is stable across compilers, and sees neither
+/// comments nor formatting.
+fn body_hash(block: &syn::Block) -> String {
+    let hash = quote! { #block }.to_string().bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+    });
+    format!("{:016x}", hash)
+}
+
+/// Splits a cache path template into its leading segments without placeholders and the remaining segments, e.g.
+/// `"./cache/{arg}/data"` into `"./cache"` and `"{arg}/data"`. An escaped `{{` starts no placeholder, and stays escaped
+/// in both parts.
+fn split_template(template: &str) -> (String, String) {
+    let segments: Vec<&str> = template.split('/').collect();
+    let split = segments.iter().position(|segment| segment.replace("{{", "").contains('{')).unwrap_or(segments.len());
+    (segments[..split].join("/"), segments[split..].join("/"))
+}
+
+/// The path a part of a template without placeholders stands for, with its escaped braces made literal.
+fn unescape_braces(template: &str) -> String {
+    template.replace("{{", "{").replace("}}", "}")
+}
+
+/// Whether `path` starts with `~` standing for the home directory, which `disk_cache::file::expand_home` replaces at
+/// runtime.
+fn starts_at_home(path: &str) -> bool {
+    path.strip_prefix('~').is_some_and(|rest| rest.is_empty() || rest.starts_with(['/', '\\']))
+}
+
+/// Parses a duration attribute into seconds: either an integer number of seconds, or a string such as `"30m"` with one
+/// of the `s`, `m`, `h`, `d` or `w` suffixes. Durations beyond what a `chrono::Duration` holds are an error.
+fn parse_seconds(lit: &Lit) -> syn::Result<i64> {
+    // `chrono::Duration::seconds` panics beyond `i64::MAX` milliseconds
+    const MAX_SECONDS: i64 = i64::MAX / 1000;
+    let seconds = match lit {
+        Lit::Int(lit_int) => lit_int.base10_parse::<i64>()?,
+        Lit::Str(lit_str) => {
+            let value = lit_str.value();
+            let (amount, unit) = value.split_at(value.len() - value.chars().last().map_or(0, char::len_utf8));
+            let multiplier = match unit {
+                "s" => 1,
+                "m" => 60,
+                "h" => 60 * 60,
+                "d" => 24 * 60 * 60,
+                "w" => 7 * 24 * 60 * 60,
+                _ => 0,
+            };
+            match amount.parse::<i64>() {
+                Ok(amount) if multiplier != 0 && amount >= 0 => {
+                    amount.checked_mul(multiplier).ok_or_else(|| syn::Error::new_spanned(lit, "duration too large"))?
+                },
+                _ => {
+                    return Err(syn::Error::new_spanned(
+                        lit_str,
+                        format!("invalid duration `{}`, expected a number followed by one of `s`, `m`, `h`, `d`, `w`", value),
+                    ))
+                },
+            }
+        },
+        lit => return Err(syn::Error::new_spanned(lit, "expected a number of seconds or a duration string such as `\"30m\"`")),
+    };
+    if seconds > MAX_SECONDS {
+        return Err(syn::Error::new_spanned(lit, "duration too large"));
+    }
+    Ok(seconds)
+}
+
+/// Collects the binding identifier of every function argument, in order. A `self` receiver is not part of the key and
+/// is skipped.
+fn arg_idents(inputs: &syn::punctuated::Punctuated<syn::FnArg, syn::token::Comma>) -> syn::Result<Vec<&syn::Ident>> {
+    inputs
+        .iter()
+        .filter_map(|arg| match arg {
+            syn::FnArg::Typed(pat_type) => Some(pat_type),
+            syn::FnArg::Receiver(_) => None,
+        })
+        .map(|pat_type| match &*pat_type.pat {
+            syn::Pat::Ident(pat_ident) => Ok(&pat_ident.ident),
+            pat => Err(syn::Error::new_spanned(pat, "`key = \"args\"` requires every argument to be a plain identifier")),
+        })
+        .collect()
+}
+
+/// Emits a `tracing` event at `level` when the `tracing` feature is enabled, and nothing otherwise.
+fn trace_event(level: proc_macro2::TokenStream, fields: proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+    if cfg!(feature = "tracing") {
+        quote! { disk_cache::tracing::#level!(#fields); }
+    } else {
+        quote! {}
+    }
+}
+
+/// Reports an attribute whose value is the wrong kind of literal
+fn expected_literal(nv: &syn::MetaNameValue, expected: &str) -> TokenStream {
+    let name = nv.path.get_ident().map(|ident| ident.to_string()).unwrap_or_default();
+    let message = format!("`{}` expects {} value", name, expected);
+    syn::Error::new_spanned(&nv.lit, message).to_compile_error().into()
+}
+
+/// Extracts `T` from a return type of the form `impl Future<Output = T>`.
+fn future_output_type(output: &ReturnType) -> Option<&Type> {
+    let ReturnType::Type(_, ty) = output else {
+        return None;
+    };
+    let Type::ImplTrait(impl_trait) = &**ty else {
+        return None;
+    };
+    impl_trait.bounds.iter().find_map(|bound| {
+        let syn::TypeParamBound::Trait(trait_bound) = bound else {
+            return None;
+        };
+        let segment = trait_bound.path.segments.last().filter(|segment| segment.ident == "Future")?;
+        let syn::PathArguments::AngleBracketed(args) = &segment.arguments else {
+            return None;
+        };
+        args.args.iter().find_map(|arg| match arg {
+            syn::GenericArgument::Binding(binding) if binding.ident == "Output" => Some(&binding.ty),
+            _ => None,
+        })
+    })
+}
+
+/// Finds a reference anywhere in `ty`, including in its generic arguments.
+fn find_reference(ty: &Type) -> Option<&syn::TypeReference> {
+    match ty {
+        Type::Reference(reference) => Some(reference),
+        Type::Array(array) => find_reference(&array.elem),
+        Type::Slice(slice) => find_reference(&slice.elem),
+        Type::Paren(paren) => find_reference(&paren.elem),
+        Type::Group(group) => find_reference(&group.elem),
+        Type::Tuple(tuple) => tuple.elems.iter().find_map(find_reference),
+        Type::Path(type_path) => type_path.path.segments.iter().find_map(|segment| match &segment.arguments {
+            syn::PathArguments::AngleBracketed(args) => args.args.iter().find_map(|arg| match arg {
+                syn::GenericArgument::Type(ty) => find_reference(ty),
+                _ => None,
+            }),
+            _ => None,
+        }),
+        _ => None,
+    }
+}
+
+/// Finds a trait object anywhere in `ty`, including in its generic arguments.
+fn find_trait_object(ty: &Type) -> Option<&syn::TypeTraitObject> {
+    match ty {
+        Type::TraitObject(trait_object) => Some(trait_object),
+        Type::Array(array) => find_trait_object(&array.elem),
+        Type::Slice(slice) => find_trait_object(&slice.elem),
+        Type::Paren(paren) => find_trait_object(&paren.elem),
+        Type::Group(group) => find_trait_object(&group.elem),
+        Type::Tuple(tuple) => tuple.elems.iter().find_map(find_trait_object),
+        Type::Path(type_path) => type_path.path.segments.iter().find_map(|segment| match &segment.arguments {
+            syn::PathArguments::AngleBracketed(args) => args.args.iter().find_map(|arg| match arg {
+                syn::GenericArgument::Type(ty) => find_trait_object(ty),
+                _ => None,
+            }),
+            _ => None,
+        }),
+        _ => None,
+    }
+}
+
+/// Replaces the trait object of a `Box<dyn Trait>` with `concrete`, where the box is either the whole of `ty` or the `T`
+/// of `Result<T, E>` or `Option<T>`, and returns the boxed trait object it replaced.
+fn replace_trait_object(ty: &mut Type, concrete: &syn::Path) -> Option<Type> {
+    let original = ty.clone();
+    let Type::Path(type_path) = ty else {
+        return None;
+    };
+    let segment = type_path.path.segments.last_mut()?;
+    let name = segment.ident.to_string();
+    let syn::PathArguments::AngleBracketed(args) = &mut segment.arguments else {
+        return None;
+    };
+    let first = args.args.iter_mut().find_map(|arg| match arg {
+        syn::GenericArgument::Type(ty) => Some(ty),
+        _ => None,
+    })?;
+    match name.as_str() {
+        "Box" if matches!(first, Type::TraitObject(_)) => {
+            *first = syn::parse_quote! { #concrete };
+            Some(original)
+        },
+        "Result" | "Option" => replace_trait_object(first, concrete),
+        _ => None,
+    }
+}
+
+/// The `T` of an `impl Stream<Item = T>` return type.
+fn stream_item(ty: &Type) -> Option<Type> {
+    let Type::ImplTrait(impl_trait) = ty else {
+        return None;
+    };
+    impl_trait.bounds.iter().find_map(|bound| {
+        let syn::TypeParamBound::Trait(bound) = bound else {
+            return None;
+        };
+        let segment = bound.path.segments.last().filter(|segment| segment.ident == "Stream")?;
+        let syn::PathArguments::AngleBracketed(args) = &segment.arguments else {
+            return None;
+        };
+        args.args.iter().find_map(|arg| match arg {
+            syn::GenericArgument::Binding(binding) if binding.ident == "Item" => Some(binding.ty.clone()),
+            _ => None,
+        })
+    })
+}
+
+/// The name of `ty` if it is a `HashMap` or a `HashSet`, whose serialized order changes from one run to the next.
+fn hashed_collection(ty: &Type) -> Option<&'static str> {
+    let Type::Path(type_path) = ty else {
+        return None;
+    };
+    let segment = type_path.path.segments.last()?;
+    ["HashMap", "HashSet"].into_iter().find(|name| segment.ident == name)
+}
+
+/// Whether `ty` is `Vec<u8>`, which is cached without serde.
+fn is_byte_vec(ty: &Type) -> bool {
+    let Type::Path(type_path) = ty else {
+        return false;
+    };
+    let Some(segment) = type_path.path.segments.last() else {
+        return false;
+    };
+    let syn::PathArguments::AngleBracketed(args) = &segment.arguments else {
+        return false;
+    };
+    match (segment.ident.to_string().as_str(), args.args.first(), args.args.len()) {
+        ("Vec", Some(syn::GenericArgument::Type(Type::Path(elem))), 1) => elem.qself.is_none() && elem.path.is_ident("u8"),
+        _ => false,
+    }
+}
+
+/// The shape of a return type that the cache treats specially.
+enum ReturnShape<'a> {
+    /// `Result<T, E>`, with its `T` and `E`.
+    Result(&'a Type, &'a Type),
+    /// `Option<T>`, with its `T`. Both variants are cached.
+    Option(&'a Type),
+    Plain,
+}
+
+fn return_shape(output: &ReturnType) -> ReturnShape<'_> {
+    let ReturnType::Type(_, ty) = output else {
+        return ReturnShape::Plain;
+    };
+    // Match the return type as a Path
+    let Type::Path(type_path) = &**ty else {
+        return ReturnShape::Plain;
+    };
+    let Some(segment) = type_path.path.segments.last() else {
+        return ReturnShape::Plain;
+    };
+    // Extract the generic arguments, e.g. of Result<T, E>
+    let syn::PathArguments::AngleBracketed(args) = &segment.arguments else {
+        return ReturnShape::Plain;
+    };
+    let types: Vec<&Type> = args
+        .args
+        .iter()
+        .filter_map(|arg| match arg {
+            syn::GenericArgument::Type(ty) => Some(ty),
+            _ => None,
+        })
+        .collect();
+    match (segment.ident.to_string().as_str(), types.as_slice()) {
+        ("Result", [ok, err]) => ReturnShape::Result(ok, err),
+        ("Option", [some]) => ReturnShape::Option(some),
+        _ => ReturnShape::Plain,
+    }
+}
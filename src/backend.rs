@@ -0,0 +1,157 @@
+//! Storage of cache entries outside individual files: in embedded `sled` databases with `backend = "sled"`, and in
+//! Redis with `backend = "redis"`.
+
+use std::collections::HashMap;
+use std::io;
+use std::sync::{Mutex, OnceLock};
+
+/// Every database opened by this process, by root directory. `sled` locks its directory, so each one is opened once
+/// and shared by all the functions caching below it.
+#[cfg(feature = "sled")]
+static DATABASES: OnceLock<Mutex<HashMap<String, sled::Db>>> = OnceLock::new();
+
+#[cfg(feature = "sled")]
+fn database(root: &str) -> io::Result<sled::Db> {
+    let mut databases = DATABASES.get_or_init(Default::default).lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(db) = databases.get(root) {
+        return Ok(db.clone());
+    }
+    let db = sled::open(root).map_err(io::Error::other)?;
+    databases.insert(root.to_string(), db.clone());
+    Ok(db)
+}
+
+#[cfg(feature = "sled")]
+#[doc(hidden)]
+pub fn sled_contains(root: &str, key: &str) -> io::Result<bool> {
+    database(root)?.contains_key(key).map_err(io::Error::other)
+}
+
+#[cfg(feature = "sled")]
+#[doc(hidden)]
+pub fn sled_get(root: &str, key: &str) -> io::Result<Vec<u8>> {
+    match database(root)?.get(key).map_err(io::Error::other)? {
+        Some(value) => Ok(value.to_vec()),
+        None => Err(io::Error::from(io::ErrorKind::NotFound)),
+    }
+}
+
+#[cfg(feature = "sled")]
+#[doc(hidden)]
+pub fn sled_insert(root: &str, key: &str, value: &[u8]) -> io::Result<()> {
+    let db = database(root)?;
+    db.insert(key, value).map_err(io::Error::other)?;
+    // entries must survive the process exiting right after the write
+    db.flush().map(|_| ()).map_err(io::Error::other)
+}
+
+#[cfg(feature = "sled")]
+#[doc(hidden)]
+pub fn sled_remove(root: &str, key: &str) -> io::Result<()> {
+    database(root)?.remove(key).map(|_| ()).map_err(io::Error::other)
+}
+
+/// Removes every entry whose key starts with `prefix`.
+#[cfg(feature = "sled")]
+#[doc(hidden)]
+pub fn sled_remove_prefix(root: &str, prefix: &str) -> io::Result<()> {
+    let db = database(root)?;
+    for key in db.scan_prefix(prefix).keys() {
+        db.remove(key.map_err(io::Error::other)?).map_err(io::Error::other)?;
+    }
+    db.flush().map(|_| ()).map_err(io::Error::other)
+}
+
+/// A connection to every Redis server used by this process, by URL. The managers reconnect on their own, and are
+/// cheap to clone.
+#[cfg(feature = "redis")]
+static CONNECTIONS: OnceLock<Mutex<HashMap<String, redis::aio::ConnectionManager>>> = OnceLock::new();
+
+#[cfg(feature = "redis")]
+async fn connection(url: &str) -> io::Result<redis::aio::ConnectionManager> {
+    let connections = CONNECTIONS.get_or_init(Default::default);
+    if let Some(connection) = connections.lock().unwrap_or_else(|e| e.into_inner()).get(url) {
+        return Ok(connection.clone());
+    }
+    let client = redis::Client::open(url).map_err(io::Error::other)?;
+    let connection = redis::aio::ConnectionManager::new(client).await.map_err(io::Error::other)?;
+    // another task may have connected in the meantime, in which case its connection is kept
+    let mut connections = connections.lock().unwrap_or_else(|e| e.into_inner());
+    Ok(connections.entry(url.to_string()).or_insert(connection).clone())
+}
+
+#[cfg(feature = "redis")]
+#[doc(hidden)]
+pub async fn redis_contains(url: &str, key: &str) -> io::Result<bool> {
+    let mut connection = connection(url).await?;
+    redis::AsyncCommands::exists(&mut connection, key).await.map_err(io::Error::other)
+}
+
+#[cfg(feature = "redis")]
+#[doc(hidden)]
+pub async fn redis_get(url: &str, key: &str) -> io::Result<Vec<u8>> {
+    let mut connection = connection(url).await?;
+    let value: Option<Vec<u8>> = redis::AsyncCommands::get(&mut connection, key).await.map_err(io::Error::other)?;
+    value.ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))
+}
+
+/// Stores `value` under `key`, expiring after `ttl_seconds`, or never when it is not positive.
+#[cfg(feature = "redis")]
+#[doc(hidden)]
+pub async fn redis_insert(url: &str, key: &str, value: &[u8], ttl_seconds: i64) -> io::Result<()> {
+    let mut connection = connection(url).await?;
+    let stored: redis::RedisResult<()> = match u64::try_from(ttl_seconds) {
+        Ok(ttl_seconds) if ttl_seconds > 0 => redis::AsyncCommands::set_ex(&mut connection, key, value, ttl_seconds).await,
+        _ => redis::AsyncCommands::set(&mut connection, key, value).await,
+    };
+    stored.map_err(io::Error::other)
+}
+
+#[cfg(feature = "redis")]
+#[doc(hidden)]
+pub async fn redis_remove(url: &str, key: &str) -> io::Result<()> {
+    let mut connection = connection(url).await?;
+    let removed: redis::RedisResult<()> = redis::AsyncCommands::del(&mut connection, key).await;
+    removed.map_err(io::Error::other)
+}
+
+/// Removes every entry whose key starts with `prefix`.
+#[cfg(feature = "redis")]
+#[doc(hidden)]
+pub async fn redis_remove_prefix(url: &str, prefix: &str) -> io::Result<()> {
+    let mut connection = connection(url).await?;
+    // the prefix is matched literally, rather than as a glob
+    let mut pattern: String = prefix.chars().flat_map(|c| match c {
+        '*' | '?' | '[' | ']' | '\\' => vec!['\\', c],
+        c => vec![c],
+    }).collect();
+    pattern.push('*');
+    let keys: Vec<String> = {
+        let mut keys = Vec::new();
+        let mut iter = redis::AsyncCommands::scan_match::<_, String>(&mut connection, pattern).await.map_err(io::Error::other)?;
+        while let Some(key) = iter.next_item().await {
+            keys.push(key);
+        }
+        keys
+    };
+    if keys.is_empty() {
+        return Ok(());
+    }
+    let removed: redis::RedisResult<()> = redis::AsyncCommands::del(&mut connection, keys).await;
+    removed.map_err(io::Error::other)
+}
+
+/// Copies the entry at `path` from the server to disk when it is missing there, for `backend = "files+redis"`, where
+/// both tiers hold the same bytes. It is then read from disk like any other.
+#[cfg(feature = "redis")]
+#[doc(hidden)]
+pub async fn redis_fetch(url: &str, path: &str) -> io::Result<()> {
+    if tokio::fs::try_exists(path).await? {
+        return Ok(());
+    }
+    match redis_get(url, path).await {
+        Ok(data) => crate::file::write_atomic_async(path, crate::file::tmp_path(path), &data).await,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e),
+    }
+}
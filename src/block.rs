@@ -0,0 +1,8 @@
+//! Running blocks of the generated code in a closure of their own, so that `return` and `?` in them stay in the block.
+
+/// Calls `block`. Passing the closure here rather than calling it where it is written keeps clippy's
+/// `redundant_closure_call` quiet in the expansion, which carries the spans of the user's code.
+#[doc(hidden)]
+pub fn run<T>(block: impl FnOnce() -> T) -> T {
+    block()
+}
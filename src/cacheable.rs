@@ -0,0 +1,17 @@
+//! The check that the return type of a cached function can be serialized, reported at the return type.
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Implemented by every type that can be cached with a serde `format`.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` cannot be cached",
+    label = "cached types must implement `serde::Serialize` and `serde::de::DeserializeOwned`",
+    note = "derive them with `#[derive(serde::Serialize, serde::Deserialize)]`, or encode the type with `serialize_with` and `deserialize_with`"
+)]
+pub trait Cacheable {}
+
+// the impl is not suggested, so that the error names the return type rather than listing the implementors of serde's
+// traits
+#[diagnostic::do_not_recommend]
+impl<T: Serialize + DeserializeOwned> Cacheable for T {}
@@ -0,0 +1,51 @@
+//! Encryption of cache entries with `encrypt = true`: ChaCha20-Poly1305 with a key read from the environment, and a
+//! random nonce written in front of every entry.
+
+use std::io;
+
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+
+use crate::CacheError;
+
+const NONCE_LEN: usize = 12;
+
+/// Reads the key held by the environment variable `env`, 32 bytes written as 64 hex digits. Returns `None` when it is
+/// unset or invalid.
+#[doc(hidden)]
+pub fn key(env: &str) -> Option<Key> {
+    let hex = std::env::var(env).ok()?;
+    let hex = hex.trim();
+    if hex.len() != 64 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let mut key = [0u8; 32];
+    for (byte, digits) in key.iter_mut().zip(hex.as_bytes().chunks(2)) {
+        *byte = u8::from_str_radix(std::str::from_utf8(digits).ok()?, 16).ok()?;
+    }
+    Some(key.into())
+}
+
+#[doc(hidden)]
+pub fn encrypt(env: &str, data: &[u8]) -> io::Result<Vec<u8>> {
+    let key = key(env).ok_or_else(|| {
+        io::Error::new(io::ErrorKind::NotFound, format!("`{}` does not hold a key of 64 hex digits", env))
+    })?;
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = ChaCha20Poly1305::new(&key)
+        .encrypt(&nonce, data)
+        .map_err(|_| io::Error::other("failed to encrypt cache entry"))?;
+    Ok([nonce.as_slice(), &ciphertext].concat())
+}
+
+/// Fails with [`CacheError::Deserialize`] when the entry was written with another key or was tampered with.
+#[doc(hidden)]
+pub fn decrypt(key: &Key, data: &[u8]) -> Result<Vec<u8>, CacheError> {
+    if data.len() < NONCE_LEN {
+        return Err(CacheError::Deserialize("encrypted cache entry is missing its nonce".into()));
+    }
+    let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+    ChaCha20Poly1305::new(key)
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| CacheError::Deserialize("cache entry could not be decrypted with the current key".into()))
+}
@@ -0,0 +1,116 @@
+//! Hashers turning the arguments of a function with `key = "args"` into the name of its entry's directory. Their output
+//! is stable across targets, as integers are hashed as little-endian bytes and `usize` and `isize` as 64 bits. The
+//! arguments reach them through `std::hash::Hash`, whose output for standard library types is not guaranteed between
+//! Rust versions, so that entries may be missed after upgrading the compiler.
+
+use std::hash::Hasher;
+
+/// The integer methods of `Hasher`, which by default write native-endian bytes, and `usize` and `isize`, including the
+/// length of slices and collections, as wide as the target's pointers.
+macro_rules! little_endian_integers {
+    ($($method:ident($type:ty) as $written:ty),* $(,)?) => {
+        $(
+            fn $method(&mut self, value: $type) {
+                self.write(&(value as $written).to_le_bytes());
+            }
+        )*
+    };
+    () => {
+        little_endian_integers! {
+            write_u16(u16) as u16,
+            write_u32(u32) as u32,
+            write_u64(u64) as u64,
+            write_u128(u128) as u128,
+            write_usize(usize) as u64,
+            write_i16(i16) as i16,
+            write_i32(i32) as i32,
+            write_i64(i64) as i64,
+            write_i128(i128) as i128,
+            write_isize(isize) as i64,
+        }
+    };
+}
+
+/// XXH3, 64 bits, the default with `hash = "xxh3"`. Fast, but not collision-resistant against crafted arguments.
+#[derive(Default)]
+pub struct Xxh3(xxhash_rust::xxh3::Xxh3);
+
+impl Xxh3 {
+    /// The digest as 16 hex digits.
+    pub fn hex_digest(&self) -> String {
+        format!("{:016x}", self.0.digest())
+    }
+}
+
+impl Hasher for Xxh3 {
+    fn write(&mut self, bytes: &[u8]) {
+        self.0.update(bytes);
+    }
+
+    little_endian_integers!();
+
+    fn finish(&self) -> u64 {
+        self.0.digest()
+    }
+}
+
+/// SHA-256, with `hash = "sha256"` and the `sha256` feature.
+#[cfg(feature = "sha256")]
+#[derive(Default)]
+pub struct Sha256(sha2::Sha256);
+
+#[cfg(feature = "sha256")]
+impl Sha256 {
+    /// The digest as 64 hex digits.
+    pub fn hex_digest(&self) -> String {
+        hex(&sha2::Digest::finalize(self.0.clone()))
+    }
+}
+
+#[cfg(feature = "sha256")]
+impl Hasher for Sha256 {
+    fn write(&mut self, bytes: &[u8]) {
+        sha2::Digest::update(&mut self.0, bytes);
+    }
+
+    little_endian_integers!();
+
+    /// The first 8 bytes of the digest.
+    fn finish(&self) -> u64 {
+        let digest = sha2::Digest::finalize(self.0.clone());
+        u64::from_be_bytes(digest[..8].try_into().unwrap())
+    }
+}
+
+/// BLAKE3, 256 bits, with `hash = "blake3"` and the `blake3` feature.
+#[cfg(feature = "blake3")]
+#[derive(Default)]
+pub struct Blake3(blake3::Hasher);
+
+#[cfg(feature = "blake3")]
+impl Blake3 {
+    /// The digest as 64 hex digits.
+    pub fn hex_digest(&self) -> String {
+        self.0.finalize().to_hex().to_string()
+    }
+}
+
+#[cfg(feature = "blake3")]
+impl Hasher for Blake3 {
+    fn write(&mut self, bytes: &[u8]) {
+        self.0.update(bytes);
+    }
+
+    little_endian_integers!();
+
+    /// The first 8 bytes of the digest.
+    fn finish(&self) -> u64 {
+        let digest = self.0.finalize();
+        u64::from_be_bytes(digest.as_bytes()[..8].try_into().unwrap())
+    }
+}
+
+#[cfg(feature = "sha256")]
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
@@ -0,0 +1,77 @@
+//! Checksums appended to every entry with `integrity`, so that an entry corrupted on disk is detected before it is
+//! decoded, rather than deserializing into garbage.
+
+use crate::CacheError;
+
+/// A checksum of the bytes of an entry, written after them.
+pub trait Checksum {
+    /// The length of the checksum, in bytes.
+    const LEN: usize;
+
+    /// The checksum of `data`, `LEN` bytes long.
+    fn checksum(data: &[u8]) -> Vec<u8>;
+}
+
+/// CRC-32 (IEEE), 4 bytes, with `integrity = "crc32"`. Fast, and catches random corruption such as flipped bits.
+pub struct Crc32;
+
+/// The CRC-32 of every byte, for the reflected polynomial `0xEDB88320`.
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut byte = 0;
+    while byte < 256 {
+        let mut crc = byte as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+            bit += 1;
+        }
+        table[byte] = crc;
+        byte += 1;
+    }
+    table
+};
+
+impl Checksum for Crc32 {
+    const LEN: usize = 4;
+
+    fn checksum(data: &[u8]) -> Vec<u8> {
+        let crc = data.iter().fold(!0u32, |crc, &byte| (crc >> 8) ^ CRC32_TABLE[((crc ^ u32::from(byte)) & 0xFF) as usize]);
+        (!crc).to_le_bytes().to_vec()
+    }
+}
+
+/// BLAKE3, 32 bytes, with `integrity = "blake3"` and the `blake3` feature. Also catches deliberate tampering by
+/// anyone who cannot rewrite the checksum.
+#[cfg(feature = "blake3")]
+pub struct Blake3;
+
+#[cfg(feature = "blake3")]
+impl Checksum for Blake3 {
+    const LEN: usize = blake3::OUT_LEN;
+
+    fn checksum(data: &[u8]) -> Vec<u8> {
+        blake3::hash(data).as_bytes().to_vec()
+    }
+}
+
+/// Appends the checksum of `data` to it.
+pub fn with_footer<C: Checksum>(data: &[u8]) -> Vec<u8> {
+    let mut footed = Vec::with_capacity(data.len() + C::LEN);
+    footed.extend_from_slice(data);
+    footed.extend_from_slice(&C::checksum(data));
+    footed
+}
+
+/// Removes the checksum from the end of `data` after checking it. An entry too short to hold one, or whose bytes do
+/// not match it, fails with [`CacheError::Deserialize`], so that it is handled like a corrupt one.
+pub fn strip_footer<C: Checksum>(mut data: Vec<u8>) -> Result<Vec<u8>, CacheError> {
+    let Some(split) = data.len().checked_sub(C::LEN) else {
+        return Err(CacheError::Deserialize("cache entry is missing its checksum".into()));
+    };
+    if C::checksum(&data[..split]) != data[split..] {
+        return Err(CacheError::Deserialize("cache entry does not match its checksum".into()));
+    }
+    data.truncate(split);
+    Ok(data)
+}
@@ -0,0 +1,44 @@
+//! Advisory file locks held with `lock = true` while a cold entry is computed and written, so that processes sharing a
+//! cache root compute each entry once.
+
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+use fs4::fs_std::FileExt;
+
+/// An exclusive lock on the lock file of an entry, released when dropped.
+#[doc(hidden)]
+pub struct EntryLock {
+    file: File,
+}
+
+impl Drop for EntryLock {
+    fn drop(&mut self) {
+        let _ = FileExt::unlock(&self.file);
+    }
+}
+
+/// Blocks until this process holds the lock of the entry at `cache_path`, on the file next to it suffixed `.lock`. The
+/// lock file is created along with its directory, and left in place, as removing it could let another process lock a
+/// new file while a third still holds the old one.
+#[doc(hidden)]
+pub fn acquire(cache_path: &str) -> io::Result<EntryLock> {
+    let lock_path = format!("{}.lock", cache_path);
+    if let Some(parent) = Path::new(&lock_path).parent() {
+        match std::fs::create_dir_all(parent) {
+            Err(e) if e.kind() != io::ErrorKind::AlreadyExists => return Err(e),
+            _ => {},
+        }
+    }
+    let file = File::options().read(true).write(true).create(true).truncate(false).open(&lock_path)?;
+    file.lock_exclusive()?;
+    Ok(EntryLock { file })
+}
+
+/// Like [`acquire`], waiting on tokio's blocking thread pool.
+#[doc(hidden)]
+pub async fn acquire_async(cache_path: &str) -> io::Result<EntryLock> {
+    let cache_path = cache_path.to_string();
+    tokio::task::spawn_blocking(move || acquire(&cache_path)).await.map_err(io::Error::other)?
+}
@@ -0,0 +1,31 @@
+//! Maintenance of cache roots, for entries that are no longer read.
+
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::prune;
+
+/// Removes every entry below `root` written at least `ttl` ago, whatever function wrote it, and returns how many were
+/// removed. Directories left empty are removed as well.
+///
+/// Entries are the files named `data.*`, or every file below `root` with `any_name`, as needed for functions with
+/// `file_name`, except the temp files of writes in progress and the lock files of `lock = true`. The write time of a
+/// JSON entry is read from it, including the jitter of `invalidate_jitter`, and that of any other entry, e.g. compressed
+/// or in another format, is its modification time. A missing `root` holds no entries. The tree is walked on the blocking
+/// thread pool of tokio, or of async-std with the `async-std` feature.
+///
+/// ```no_run
+/// # async fn example() -> std::io::Result<()> {
+/// let removed = disk_cache::purge_expired(std::path::Path::new("./cache"), std::time::Duration::from_secs(24 * 3600), false).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn purge_expired(root: &Path, ttl: Duration, any_name: bool) -> Result<usize, io::Error> {
+    let root = root.to_path_buf();
+    #[cfg(not(feature = "async-std"))]
+    let removed = tokio::task::spawn_blocking(move || prune::expired(root, ttl, any_name)).await.map_err(io::Error::other)?;
+    #[cfg(feature = "async-std")]
+    let removed = async_std::task::spawn_blocking(move || prune::expired(root, ttl, any_name)).await;
+    removed
+}
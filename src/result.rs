@@ -0,0 +1,12 @@
+//! The parts of a `Result`, named through a type alias with `is_result = true`.
+
+/// Implemented by every `Result<T, E>`, to name its `T` and `E` from a type alias of it.
+pub trait ResultType {
+    type Ok;
+    type Err;
+}
+
+impl<T, E> ResultType for Result<T, E> {
+    type Ok = T;
+    type Err = E;
+}
@@ -0,0 +1,58 @@
+//! Caching of values computed at runtime, for keys that are only known when the value is needed.
+
+use std::future::Future;
+use std::path::Path;
+use std::time::Duration;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::file;
+use crate::CacheError;
+
+/// Returns the value cached in the directory `key` if it was written less than `ttl` ago, and otherwise awaits the
+/// future returned by `compute` and caches its value there before returning it.
+///
+/// Entries are stored like those of [`cache_async`](crate::cache_async) with its defaults, in `data.json` inside
+/// `key`, so that a function with `cache_root = "./cache/users/{id}"` and `flat = true` shares its entries with
+/// `cached(format!("./cache/users/{}", id), ...)`. Corrupt entries are treated as misses and removed, and a failed
+/// write is reported on stderr while the value is still returned. It must be called from within a tokio runtime.
+///
+/// ```no_run
+/// # async fn example(id: u32) -> Result<(), disk_cache::CacheError> {
+/// let name: String = disk_cache::cached(format!("./cache/users/{}", id), std::time::Duration::from_secs(3600), || async move {
+///     format!("user {}", id)
+/// })
+/// .await?;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn cached<T, F, Fut>(key: impl AsRef<Path>, ttl: Duration, compute: F) -> Result<T, CacheError>
+where
+    T: Serialize + DeserializeOwned,
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = T>,
+{
+    if cfg!(feature = "disabled") {
+        return Ok(compute().await);
+    }
+    let cache_path = file::entry_path(key);
+    if let Some(result) = file::read_if_valid_async(&cache_path, ttl).await? {
+        #[cfg(feature = "metrics")]
+        crate::stats::record_hit();
+        return Ok(result);
+    }
+    #[cfg(feature = "metrics")]
+    crate::stats::record_miss();
+    let result = compute().await;
+    match file::write_async(&cache_path, &result).await {
+        Err(CacheError::Io(e)) => {
+            eprintln!("failed to write cache file {}: {}", cache_path.display(), e);
+            #[cfg(feature = "metrics")]
+            crate::stats::record_write_error();
+        },
+        Err(e) => return Err(e),
+        Ok(()) => {},
+    }
+    Ok(result)
+}
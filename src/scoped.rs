@@ -0,0 +1,58 @@
+//! Temporary cache roots, removed once they are no longer used.
+
+use std::ffi::OsString;
+use std::io;
+use std::path::Path;
+
+/// Creates a unique, empty directory in the system's temp directory, to be used as a cache root. The directory and
+/// everything cached below it are removed when the returned guard is dropped.
+///
+/// Cached functions read their root at runtime through `cache_root_env`, so that exporting the directory with
+/// [`TempCacheGuard::export`] isolates them, e.g. in a test:
+///
+/// ```no_run
+/// # fn main() -> std::io::Result<()> {
+/// let mut root = disk_cache::scoped_cache_root()?;
+/// root.export("MY_CACHE_ROOT");
+/// // functions with `cache_root_env = "MY_CACHE_ROOT"` now cache below `root.path()`
+/// # Ok(())
+/// # }
+/// ```
+pub fn scoped_cache_root() -> io::Result<TempCacheGuard> {
+    let dir = tempfile::Builder::new().prefix("disk_cache").tempdir()?;
+    Ok(TempCacheGuard { dir, exported: Vec::new() })
+}
+
+/// A temporary cache root, created by [`scoped_cache_root`]. Dropping it removes the directory, and restores the
+/// environment variables it was exported to.
+#[derive(Debug)]
+pub struct TempCacheGuard {
+    dir: tempfile::TempDir,
+    /// Every exported variable, with its value beforehand.
+    exported: Vec<(String, Option<OsString>)>,
+}
+
+impl TempCacheGuard {
+    /// The directory to cache below.
+    pub fn path(&self) -> &Path {
+        self.dir.path()
+    }
+
+    /// Sets the environment variable `var` to the directory until the guard is dropped. The environment is shared by
+    /// the whole process, so tests running in parallel should each export a variable of their own.
+    pub fn export(&mut self, var: &str) {
+        self.exported.push((var.to_string(), std::env::var_os(var)));
+        std::env::set_var(var, self.dir.path());
+    }
+}
+
+impl Drop for TempCacheGuard {
+    fn drop(&mut self) {
+        for (var, previous) in self.exported.drain(..).rev() {
+            match previous {
+                Some(value) => std::env::set_var(var, value),
+                None => std::env::remove_var(var),
+            }
+        }
+    }
+}
@@ -0,0 +1,54 @@
+//! Process-global counters of cache activity, updated by every cached function when the `metrics` feature is enabled.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+static HITS: AtomicU64 = AtomicU64::new(0);
+static MISSES: AtomicU64 = AtomicU64::new(0);
+static WRITE_ERRORS: AtomicU64 = AtomicU64::new(0);
+
+/// The counters at one point in time, as returned by [`snapshot`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Calls answered from the cache, including stale entries served while they are refreshed.
+    pub hits: u64,
+    /// Calls that ran the function, including bypassed lookups.
+    pub misses: u64,
+    /// Cache files that could not be written.
+    pub write_errors: u64,
+}
+
+impl CacheStats {
+    /// Fraction of lookups that were hits, or `0.0` before the first lookup.
+    pub fn hit_rate(&self) -> f64 {
+        let lookups = self.hits + self.misses;
+        if lookups == 0 {
+            0.0
+        } else {
+            self.hits as f64 / lookups as f64
+        }
+    }
+}
+
+/// Reads the current value of every counter.
+pub fn snapshot() -> CacheStats {
+    CacheStats {
+        hits: HITS.load(Ordering::Relaxed),
+        misses: MISSES.load(Ordering::Relaxed),
+        write_errors: WRITE_ERRORS.load(Ordering::Relaxed),
+    }
+}
+
+#[doc(hidden)]
+pub fn record_hit() {
+    HITS.fetch_add(1, Ordering::Relaxed);
+}
+
+#[doc(hidden)]
+pub fn record_miss() {
+    MISSES.fetch_add(1, Ordering::Relaxed);
+}
+
+#[doc(hidden)]
+pub fn record_write_error() {
+    WRITE_ERRORS.fetch_add(1, Ordering::Relaxed);
+}
@@ -0,0 +1,13 @@
+//! Streams cached with `collect = true`, which are collected into a `Vec` and streamed back out of it.
+
+use futures_util::stream::{self, Iter, Stream, StreamExt};
+
+/// Collects every item of `stream`, in order.
+pub async fn collect<S: Stream>(stream: S) -> Vec<S::Item> {
+    stream.collect().await
+}
+
+/// Returns a stream yielding the cached `items`, in order.
+pub fn iter<T>(items: Vec<T>) -> Iter<std::vec::IntoIter<T>> {
+    stream::iter(items)
+}
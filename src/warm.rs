@@ -0,0 +1,36 @@
+//! Priming many entries at once, e.g. when warming a cache at deploy time.
+
+use std::future::Future;
+
+use futures_util::stream::{self, StreamExt};
+
+/// Calls `prime` with each of `args`, running up to `concurrency` of the returned futures at a time, and returns their
+/// results in the order of `args`, so that the keys that failed can be told apart and retried.
+///
+/// `prime` is typically a call to the `<name>_prime` function generated by the macros. A `concurrency` of 0 is treated
+/// as 1.
+///
+/// ```no_run
+/// # async fn fetch_user_prime(id: u32) -> Result<(), disk_cache::CacheError> { Ok(()) }
+/// # async fn example() {
+/// let results = disk_cache::warm_many(0..1000, 16, |id| fetch_user_prime(id)).await;
+/// let failed = results.iter().filter(|result| result.is_err()).count();
+/// # }
+/// ```
+pub async fn warm_many<A, I, F, Fut, E>(args: I, concurrency: usize, mut prime: F) -> Vec<Result<(), E>>
+where
+    I: IntoIterator<Item = A>,
+    F: FnMut(A) -> Fut,
+    Fut: Future<Output = Result<(), E>>,
+{
+    let mut results: Vec<(usize, Result<(), E>)> = stream::iter(args.into_iter().enumerate())
+        .map(|(index, args)| {
+            let primed = prime(args);
+            async move { (index, primed.await) }
+        })
+        .buffer_unordered(concurrency.max(1))
+        .collect()
+        .await;
+    results.sort_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, result)| result).collect()
+}
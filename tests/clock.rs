@@ -0,0 +1,26 @@
+#![cfg(feature = "test-clock")]
+
+use disk_cache::cache;
+
+#[cache(cache_root = "./cache/clock/{arg}", invalidate_rate = 60, write_mode = "blocking")]
+fn expensive_function_clock(arg: i32) -> String {
+    format!("Hello {}", arg)
+}
+
+#[test]
+fn check_clock(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/clock").unwrap_or_default();
+    assert_eq!(expensive_function_clock(740).unwrap(), "Hello 740");
+    assert!(expensive_function_clock_is_cached(740).unwrap());
+    // the entry expires once the clock moves past its invalidate rate, without sleeping
+    disk_cache::clock::advance(std::time::Duration::from_secs(30));
+    assert!(expensive_function_clock_is_cached(740).unwrap());
+    disk_cache::clock::advance(std::time::Duration::from_secs(31));
+    assert!(!expensive_function_clock_is_cached(740).unwrap());
+    let (_, meta) = expensive_function_clock_with_meta(740).unwrap();
+    assert!(!meta.hit);
+    // and the entry rewritten meanwhile is still valid once the clock is back
+    disk_cache::clock::reset();
+    assert!(expensive_function_clock_is_cached(740).unwrap());
+}
@@ -0,0 +1,28 @@
+#![cfg(feature = "encrypt")]
+
+use disk_cache::cache;
+
+#[cache(cache_root = "./cache/encrypt/{arg}", invalidate_rate = 3600, encrypt = true, encrypt_key_env = "DISK_CACHE_TEST_KEY", write_mode = "blocking")]
+fn expensive_function_encrypt(arg: i32) -> String {
+    format!("secret {}", arg)
+}
+
+#[test]
+fn check_encrypt(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/encrypt/expensive_function_encrypt/440").unwrap_or_default();
+    std::env::set_var("DISK_CACHE_TEST_KEY", "00112233445566778899aabbccddeeff00112233445566778899aabbccddeeff");
+    assert_eq!(expensive_function_encrypt(440).unwrap(), "secret 440");
+    assert!(expensive_function_encrypt_is_cached(440).unwrap());
+    // the payload is not stored in plaintext
+    let data = std::fs::read("./cache/encrypt/expensive_function_encrypt/440/data.json").unwrap();
+    assert!(!String::from_utf8_lossy(&data).contains("secret"));
+    assert_eq!(expensive_function_encrypt(440).unwrap(), "secret 440");
+    // with another key, the entry cannot be read
+    std::env::set_var("DISK_CACHE_TEST_KEY", "ff112233445566778899aabbccddeeff00112233445566778899aabbccddeeff");
+    assert!(!expensive_function_encrypt_is_cached(440).unwrap());
+    // and without a key, every lookup is a miss
+    std::env::remove_var("DISK_CACHE_TEST_KEY");
+    assert!(!expensive_function_encrypt_is_cached(440).unwrap());
+    assert_eq!(expensive_function_encrypt(440).unwrap(), "secret 440");
+}
@@ -0,0 +1,56 @@
+#![cfg(all(feature = "sha256", feature = "blake3"))]
+
+use disk_cache::cache;
+
+#[cache(cache_root = "./cache/hash_sha256", invalidate_rate = 3600, key = "args", hash = "sha256", write_mode = "blocking")]
+fn expensive_function_sha256(arg: i32) -> String {
+    format!("Hello {}", arg)
+}
+
+#[cache(cache_root = "./cache/hash_blake3", invalidate_rate = 3600, key = "args", hash = "blake3", write_mode = "blocking")]
+fn expensive_function_blake3(arg: i32) -> String {
+    format!("Hello {}", arg)
+}
+
+#[cache(cache_root = "./cache/integrity_blake3/{arg}", invalidate_rate = 3600, integrity = "blake3", write_mode = "blocking")]
+fn expensive_function_integrity_blake3(arg: i32) -> String {
+    format!("Hello {}", arg)
+}
+
+#[test]
+fn check_hash(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/hash_sha256").unwrap_or_default();
+    std::fs::remove_dir_all("./cache/hash_blake3").unwrap_or_default();
+    assert_eq!(expensive_function_sha256(770).unwrap(), "Hello 770");
+    assert_eq!(expensive_function_blake3(770).unwrap(), "Hello 770");
+    // both digests are 256 bits, written as 64 hex digits
+    for path in [expensive_function_sha256_cache_path(770), expensive_function_blake3_cache_path(770)] {
+        assert!(path.exists());
+        let key = path.parent().unwrap().file_name().unwrap().to_str().unwrap().to_string();
+        assert_eq!(key.len(), 64);
+        assert!(key.chars().all(|c| c.is_ascii_hexdigit()));
+    }
+    assert_ne!(expensive_function_sha256_cache_path(770).parent().unwrap().file_name(), expensive_function_blake3_cache_path(770).parent().unwrap().file_name());
+    assert!(expensive_function_sha256_is_cached(770).unwrap());
+    assert!(expensive_function_blake3_is_cached(770).unwrap());
+}
+
+#[test]
+fn check_integrity_blake3(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/integrity_blake3").unwrap_or_default();
+    assert_eq!(expensive_function_integrity_blake3(771).unwrap(), "Hello 771");
+    // the entry ends with the 32 bytes of its BLAKE3 digest
+    let path = expensive_function_integrity_blake3_cache_path(771);
+    let mut data = std::fs::read(&path).unwrap();
+    let split = data.len() - 32;
+    assert_eq!(&data[split..], blake3::hash(&data[..split]).as_bytes());
+    assert!(expensive_function_integrity_blake3_is_cached(771).unwrap());
+    // a flipped bit is a miss
+    data[0] ^= 1;
+    std::fs::write(&path, data).unwrap();
+    assert!(!expensive_function_integrity_blake3_is_cached(771).unwrap());
+    assert_eq!(expensive_function_integrity_blake3(771).unwrap(), "Hello 771");
+    assert!(expensive_function_integrity_blake3_is_cached(771).unwrap());
+}
@@ -0,0 +1,25 @@
+#![cfg(feature = "lock")]
+
+use disk_cache::cache;
+
+static CALLS: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+#[cache(cache_root = "./cache/lock/{arg}", invalidate_rate = 3600, lock = true)]
+fn expensive_function_lock(arg: i32) -> String {
+    CALLS.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+    std::thread::sleep(std::time::Duration::from_millis(200));
+    format!("Hello {}", arg)
+}
+
+#[test]
+fn check_lock(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/lock").unwrap_or_default();
+    // each thread opens the lock file on its own, so they contend for it like separate processes would
+    let handles: Vec<_> = (0..4).map(|_| std::thread::spawn(|| expensive_function_lock(750).unwrap())).collect();
+    for handle in handles {
+        assert_eq!(handle.join().unwrap(), "Hello 750");
+    }
+    assert_eq!(CALLS.load(std::sync::atomic::Ordering::SeqCst), 1);
+    assert!(std::path::Path::new("./cache/lock/expensive_function_lock/750/data.json.lock").exists());
+}
@@ -0,0 +1,62 @@
+#![cfg(feature = "redis")]
+
+// these tests need a Redis server listening on 127.0.0.1:6379
+
+use disk_cache::cache_async;
+
+#[cache_async(cache_root = "./cache/redis/{arg}", invalidate_rate = 3600, backend = "redis", redis_url = "redis://127.0.0.1:6379/", write_mode = "blocking")]
+async fn expensive_function_redis(arg: i32) -> Result<String, tokio::io::Error> {
+    tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+    Ok(format!("redis {}", arg))
+}
+
+#[tokio::test]
+async fn check_redis_backend(){
+    clear_expensive_function_redis(440).await.unwrap();
+    let start = std::time::Instant::now();
+    assert_eq!(expensive_function_redis(440).await.unwrap().unwrap(), "redis 440");
+    assert!(expensive_function_redis_is_cached(440).await.unwrap());
+    // the second call is read from the server
+    assert_eq!(expensive_function_redis(440).await.unwrap().unwrap(), "redis 440");
+    assert!(start.elapsed() < std::time::Duration::from_millis(1900));
+    // no file is written per entry
+    assert!(std::fs::metadata("./cache/redis/expensive_function_redis/440").is_err());
+    clear_expensive_function_redis(440).await.unwrap();
+    assert!(!expensive_function_redis_is_cached(440).await.unwrap());
+}
+
+// connections are kept per URL, and each test has its own runtime, so this one names the database to get its own
+#[cache_async(cache_root = "./cache/tiered/{arg}", invalidate_rate = 3600, backend = "files+redis", redis_url = "redis://127.0.0.1:6379/0", write_mode = "blocking")]
+async fn expensive_function_tiered(arg: i32) -> Result<String, tokio::io::Error> {
+    tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+    Ok(format!("tiered {}", arg))
+}
+
+#[tokio::test]
+async fn check_tiered_backend(){
+    clear_expensive_function_tiered(450).await.unwrap();
+    assert_eq!(expensive_function_tiered(450).await.unwrap().unwrap(), "tiered 450");
+    // the entry is written to both tiers
+    let path = expensive_function_tiered_cache_path(450);
+    assert!(path.exists());
+    // without its file, it is copied back from the server
+    std::fs::remove_file(&path).unwrap();
+    let start = std::time::Instant::now();
+    assert!(expensive_function_tiered_is_cached(450).await.unwrap());
+    assert!(path.exists());
+    std::fs::remove_file(&path).unwrap();
+    let (result, meta) = expensive_function_tiered_with_meta(450).await.unwrap();
+    assert_eq!(result.unwrap(), "tiered 450");
+    assert!(meta.hit);
+    assert!(start.elapsed() < std::time::Duration::from_millis(900));
+    assert!(path.exists());
+    // clearing removes it from both
+    clear_expensive_function_tiered(450).await.unwrap();
+    assert!(!path.exists());
+    assert!(!expensive_function_tiered_is_cached(450).await.unwrap());
+    // and clearing them all does too, so that none is copied back from the server
+    assert_eq!(expensive_function_tiered(451).await.unwrap().unwrap(), "tiered 451");
+    expensive_function_tiered_clear_all().await.unwrap();
+    assert!(!expensive_function_tiered_cache_path(451).exists());
+    assert!(!expensive_function_tiered_is_cached(451).await.unwrap());
+}
@@ -0,0 +1,21 @@
+#![cfg(feature = "simd-json")]
+
+use disk_cache::cache;
+
+#[cache(cache_root = "./cache/simd_json/{arg}", invalidate_rate = 3600, write_mode = "blocking")]
+fn expensive_function_simd_json(arg: i32) -> Vec<String> {
+    (0..arg).map(|i| format!("Hello {}", i)).collect()
+}
+
+#[test]
+fn check_simd_json(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/simd_json").unwrap_or_default();
+    assert_eq!(expensive_function_simd_json(730).unwrap().len(), 730);
+    // the entry is plain JSON written by serde_json, and is read back by simd-json
+    let data = std::fs::read_to_string("./cache/simd_json/expensive_function_simd_json/730/data.json").unwrap();
+    assert!(serde_json::from_str::<serde_json::Value>(&data).is_ok());
+    let (result, meta) = expensive_function_simd_json_with_meta(730).unwrap();
+    assert!(meta.hit);
+    assert_eq!(result[729], "Hello 729");
+}
@@ -0,0 +1,52 @@
+#![cfg(feature = "sled")]
+
+use disk_cache::{cache, cache_async};
+
+#[cache_async(cache_root = "./cache/sled/{arg}", invalidate_rate = 3600, backend = "sled", write_mode = "blocking")]
+async fn expensive_function_sled(arg: i32) -> Result<String, tokio::io::Error> {
+    tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+    Ok(format!("sled {}", arg))
+}
+
+#[cache(cache_root = "./cache/sled/{arg}", invalidate_rate = 3600, backend = "sled", write_mode = "blocking")]
+fn expensive_function_sled_sync(arg: i32) -> String {
+    format!("sync {}", arg)
+}
+
+#[tokio::test]
+async fn check_sled_backend(){
+    clear_expensive_function_sled(430).await.unwrap();
+    let start = std::time::Instant::now();
+    assert_eq!(expensive_function_sled(430).await.unwrap().unwrap(), "sled 430");
+    assert!(expensive_function_sled_is_cached(430).await.unwrap());
+    // the second call is read from the database
+    assert_eq!(expensive_function_sled(430).await.unwrap().unwrap(), "sled 430");
+    assert!(start.elapsed() < std::time::Duration::from_millis(1900));
+    // no file is written per entry
+    assert!(std::fs::metadata("./cache/sled/430").is_err());
+    clear_expensive_function_sled(430).await.unwrap();
+    assert!(!expensive_function_sled_is_cached(430).await.unwrap());
+}
+
+#[test]
+fn check_sled_backend_shared_database(){
+    // both functions open the same database
+    clear_expensive_function_sled_sync(431).unwrap();
+    assert_eq!(expensive_function_sled_sync(431).unwrap(), "sync 431");
+    assert!(expensive_function_sled_sync_is_cached(431).unwrap());
+}
+
+#[cache(cache_root = "./cache/sled/{arg}", invalidate_rate = 3600, backend = "sled", write_mode = "blocking")]
+fn sled_cleared_function(arg: i32) -> String {
+    format!("clear {}", arg)
+}
+
+#[test]
+fn check_sled_clear_all(){
+    assert_eq!(sled_cleared_function(432).unwrap(), "clear 432");
+    assert_eq!(sled_cleared_function(433).unwrap(), "clear 433");
+    // the entries of the function are removed from the database it shares
+    sled_cleared_function_clear_all().unwrap();
+    assert!(!sled_cleared_function_is_cached(432).unwrap());
+    assert!(!sled_cleared_function_is_cached(433).unwrap());
+}
@@ -0,0 +1,22 @@
+#![cfg(feature = "metrics")]
+
+use disk_cache::cache_async;
+
+#[cache_async(cache_root = "./cache/stats/{arg}", invalidate_rate = 3600, write_mode = "blocking")]
+async fn expensive_function_stats(arg: i32) -> i32 {
+    arg
+}
+
+#[tokio::test]
+async fn check_stats(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/stats/expensive_function_stats/340").unwrap_or_default();
+    let before = disk_cache::stats::snapshot();
+    expensive_function_stats(340).await.unwrap();
+    expensive_function_stats(340).await.unwrap();
+    expensive_function_stats(340).await.unwrap();
+    let after = disk_cache::stats::snapshot();
+    assert_eq!(after.misses - before.misses, 1);
+    assert_eq!(after.hits - before.hits, 2);
+    assert_eq!(after.write_errors, before.write_errors);
+}
@@ -0,0 +1,26 @@
+#![cfg(feature = "stream")]
+
+use disk_cache::cache_async;
+use futures_util::stream::{self, Stream, StreamExt};
+
+static CALLS: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+#[cache_async(cache_root = "./cache/stream/{pages}", invalidate_rate = 3600, collect = true, write_mode = "blocking")]
+async fn expensive_function_stream(pages: u32) -> impl Stream<Item = String> {
+    CALLS.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+    stream::iter(0..pages).then(|page| async move { format!("page {}", page) })
+}
+
+#[tokio::test]
+async fn check_collect_stream(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/stream").unwrap_or_default();
+    let pages: Vec<String> = expensive_function_stream(3).await.unwrap().collect().await;
+    assert_eq!(pages, ["page 0", "page 1", "page 2"]);
+    // the items are cached as a list, and streamed back out of it in order
+    let data = std::fs::read_to_string("./cache/stream/expensive_function_stream/3/data.json").unwrap();
+    assert!(data.contains(r#"["page 0","page 1","page 2"]"#));
+    let pages: Vec<String> = expensive_function_stream(3).await.unwrap().collect().await;
+    assert_eq!(pages, ["page 0", "page 1", "page 2"]);
+    assert_eq!(CALLS.load(std::sync::atomic::Ordering::SeqCst), 1);
+}
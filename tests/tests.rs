@@ -1,5 +1,16 @@
-use tokio;
-use disk_cache::cache_async;
+use disk_cache::{cache, cache_async, cache_fallible};
+
+/// Mirrors the envelope cached payloads are stored in.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct CacheEnvelope<T> {
+    written_at: i64,
+    payload: T,
+}
+
+/// Wraps `payload` in an envelope written just now.
+fn envelope<T>(payload: T) -> CacheEnvelope<T> {
+    CacheEnvelope { written_at: chrono::Utc::now().timestamp_millis(), payload }
+}
 
 #[cache_async(cache_root = "./cache/{arg}", invalidate_rate = 3600)]
 async fn expensive_function_result(arg: i32) -> Result<String, tokio::io::Error> {
@@ -27,9 +38,11 @@ async fn check_correct_output(){
 #[tokio::test]
 async fn check_cache_created(){
     // clear the cache
-    let cache_path = "./cache/10";
+    let cache_path = "./cache/expensive_function_result/10";
     std::fs::remove_file(cache_path).unwrap_or_default();
-    let result1 = expensive_function_result(10).await.unwrap().unwrap();
+    let _result1 = expensive_function_result(10).await.unwrap().unwrap();
+    // sleep to let the cache be written
+    tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
     // make sure the cache is created
     assert!(std::fs::metadata(cache_path).is_ok());
 }
@@ -37,15 +50,15 @@ async fn check_cache_created(){
 #[tokio::test]
 async fn check_cache_hit(){
     // clear the cache
-    let cache_path = "./cache/40";
+    let cache_path = "./cache/expensive_function_result/40";
     std::fs::remove_file(cache_path).unwrap_or_default();
-    let result1 = expensive_function_result(40).await.unwrap().unwrap();
-    // make sure the cache is created
-    assert!(std::fs::metadata(cache_path).is_ok());
+    let _result1 = expensive_function_result(40).await.unwrap().unwrap();
     // sleep to let the cache be written
     tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+    // make sure the cache is created
+    assert!(std::fs::metadata(cache_path).is_ok());
     // modify the file and make sure the cache is hit
-    std::fs::write(format!("{cache_path}/data.json"), "\"Hello world\"").unwrap();
+    std::fs::write(format!("{cache_path}/data.json"), serde_json::to_string(&envelope("Hello world")).unwrap()).unwrap();
     let result2 = expensive_function_result(40).await.unwrap().unwrap();
     assert_eq!(result2, "Hello world");
 }
@@ -53,9 +66,11 @@ async fn check_cache_hit(){
 #[tokio::test]
 async fn check_cache_not_result(){
     // clear the cache
-    let cache_path = "./cache/not_result/50";
+    let cache_path = "./cache/not_result/expensive_function_not_result/50";
     std::fs::remove_file(cache_path).unwrap_or_default();
-    let result1 = expensive_function_not_result(50).await.unwrap();
+    let _result1 = expensive_function_not_result(50).await.unwrap();
+    // sleep to let the cache be written
+    tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
     // make sure the cache is created
     assert!(std::fs::metadata(cache_path).is_ok());
 }
@@ -63,15 +78,15 @@ async fn check_cache_not_result(){
 #[tokio::test]
 async fn check_cache_hit_not_result(){
     // clear the cache
-    let cache_path = "./cache/not_result/60";
+    let cache_path = "./cache/not_result/expensive_function_not_result/60";
     std::fs::remove_file(cache_path).unwrap_or_default();
-    let result1 = expensive_function_not_result(60).await.unwrap();
-    // make sure the cache is created
-    assert!(std::fs::metadata(cache_path).is_ok());
+    let _result1 = expensive_function_not_result(60).await.unwrap();
     // sleep to let the cache be written
     tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+    // make sure the cache is created
+    assert!(std::fs::metadata(cache_path).is_ok());
     // modify the file and make sure the cache is hit
-    std::fs::write(format!("{cache_path}/data.json"), "\"Hello world\"").unwrap();
+    std::fs::write(format!("{cache_path}/data.json"), serde_json::to_string(&envelope("Hello world")).unwrap()).unwrap();
     let result2 = expensive_function_not_result(60).await.unwrap();
     assert_eq!(result2, "Hello world");
 }
@@ -85,4 +100,1998 @@ async fn check_correct_output_not_result(){
     assert_eq!(result1, "Hello");
     assert_eq!(result2, "Hello");
     assert_eq!(result3, "Hello");
-}
\ No newline at end of file
+}
+#[cache_async(cache_root = "./cache/bincode/{arg}", invalidate_rate = 3600, format = "bincode")]
+async fn expensive_function_bincode(arg: i32) -> String {
+    tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
+    "Hello".to_string()
+}
+
+#[tokio::test]
+async fn check_cache_hit_bincode(){
+    // clear the cache
+    let cache_path = "./cache/bincode/expensive_function_bincode/100";
+    std::fs::remove_dir_all(cache_path).unwrap_or_default();
+    let result1 = expensive_function_bincode(100).await.unwrap();
+    assert_eq!(result1, "Hello");
+    // sleep to let the cache be written
+    tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+    assert!(std::fs::metadata(format!("{cache_path}/data.bin")).is_ok());
+    // modify the file and make sure the cache is hit
+    std::fs::write(format!("{cache_path}/data.bin"), bincode::serialize(&envelope("Hello world")).unwrap()).unwrap();
+    let result2 = expensive_function_bincode(100).await.unwrap();
+    assert_eq!(result2, "Hello world");
+}
+
+#[cache_async(cache_root = "./cache/cbor/{arg}", invalidate_rate = 3600, format = "cbor")]
+async fn expensive_function_cbor(arg: i32) -> Result<String, tokio::io::Error> {
+    tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
+    Ok("Hello".to_string())
+}
+
+#[tokio::test]
+async fn check_cache_hit_cbor(){
+    // clear the cache
+    let cache_path = "./cache/cbor/expensive_function_cbor/110";
+    std::fs::remove_dir_all(cache_path).unwrap_or_default();
+    let result1 = expensive_function_cbor(110).await.unwrap().unwrap();
+    assert_eq!(result1, "Hello");
+    // sleep to let the cache be written
+    tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+    // only the Ok value is cached
+    let mut data: Vec<u8> = Vec::new();
+    ciborium::into_writer(&envelope("Hello world"), &mut data).unwrap();
+    std::fs::write(format!("{cache_path}/data.cbor"), data).unwrap();
+    let result2 = expensive_function_cbor(110).await.unwrap().unwrap();
+    assert_eq!(result2, "Hello world");
+}
+
+#[cache(cache_root = "./cache/sync/{arg}", invalidate_rate = 3600)]
+fn expensive_function_sync(arg: i32) -> Result<String, std::io::Error> {
+    std::thread::sleep(std::time::Duration::from_secs(2));
+    Ok("Hello".to_string())
+}
+
+#[test]
+fn check_cache_hit_sync(){
+    // clear the cache
+    let cache_path = "./cache/sync/expensive_function_sync/120";
+    std::fs::remove_dir_all(cache_path).unwrap_or_default();
+    let result1 = expensive_function_sync(120).unwrap().unwrap();
+    assert_eq!(result1, "Hello");
+    // sleep to let the cache be written
+    std::thread::sleep(std::time::Duration::from_secs(1));
+    // modify the file and make sure the cache is hit
+    std::fs::write(format!("{cache_path}/data.json"), serde_json::to_string(&envelope("Hello world")).unwrap()).unwrap();
+    let result2 = expensive_function_sync(120).unwrap().unwrap();
+    assert_eq!(result2, "Hello world");
+}
+
+#[cache_async(cache_root = "./cache/key_args", invalidate_rate = 3600, key = "args")]
+async fn expensive_function_key_args(a: i32, b: String) -> String {
+    format!("{a}-{b}")
+}
+
+#[tokio::test]
+async fn check_key_args(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/key_args").unwrap_or_default();
+    let result1 = expensive_function_key_args(1, "x".to_string()).await.unwrap();
+    let result2 = expensive_function_key_args(2, "x".to_string()).await.unwrap();
+    let result3 = expensive_function_key_args(1, "y".to_string()).await.unwrap();
+    assert_eq!(result1, "1-x");
+    assert_eq!(result2, "2-x");
+    assert_eq!(result3, "1-y");
+    // sleep to let the cache be written
+    tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+    // each argument set gets its own subdirectory
+    assert_eq!(std::fs::read_dir("./cache/key_args/expensive_function_key_args").unwrap().count(), 3);
+    // and calls with the same arguments hit the cache
+    let result4 = expensive_function_key_args(1, "x".to_string()).await.unwrap();
+    assert_eq!(result4, "1-x");
+    assert_eq!(std::fs::read_dir("./cache/key_args/expensive_function_key_args").unwrap().count(), 3);
+}
+
+#[cache_async(cache_root = "./cache/atomic/{arg}", invalidate_rate = 3600)]
+async fn expensive_function_atomic(arg: i32) -> String {
+    "Hello".to_string()
+}
+
+#[tokio::test]
+async fn check_atomic_write(){
+    // clear the cache
+    let cache_path = "./cache/atomic/expensive_function_atomic/130";
+    std::fs::remove_dir_all(cache_path).unwrap_or_default();
+    std::fs::create_dir_all(cache_path).unwrap();
+    // simulate a partial write left behind by a crashed process
+    let tmp_path = format!("{cache_path}/data.json.tmp.{}.0", std::process::id());
+    std::fs::write(&tmp_path, "\"Hel").unwrap();
+    let result1 = expensive_function_atomic(130).await.unwrap();
+    assert_eq!(result1, "Hello");
+    // sleep to let the cache be written
+    tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+    // the real cache file is complete and the temp file has been renamed over it
+    let data = std::fs::read_to_string(format!("{cache_path}/data.json")).unwrap();
+    assert_eq!(serde_json::from_str::<CacheEnvelope<String>>(&data).unwrap().payload, "Hello");
+    assert!(std::fs::metadata(&tmp_path).is_err());
+    let result2 = expensive_function_atomic(130).await.unwrap();
+    assert_eq!(result2, "Hello");
+}
+
+#[cache_async(cache_root = "./cache/blocking/{arg}", invalidate_rate = 3600, write_mode = "blocking")]
+async fn expensive_function_blocking(arg: i32) -> String {
+    "Hello".to_string()
+}
+
+#[tokio::test]
+async fn check_blocking_write(){
+    // clear the cache
+    let cache_path = "./cache/blocking/expensive_function_blocking/140";
+    std::fs::remove_dir_all(cache_path).unwrap_or_default();
+    let result1 = expensive_function_blocking(140).await.unwrap();
+    assert_eq!(result1, "Hello");
+    // no sleep needed: the cache is written before the function returns
+    std::fs::write(format!("{cache_path}/data.json"), serde_json::to_string(&envelope("Hello world")).unwrap()).unwrap();
+    let result2 = expensive_function_blocking(140).await.unwrap();
+    assert_eq!(result2, "Hello world");
+}
+
+static SINGLE_FLIGHT_CALLS: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+#[cache_async(cache_root = "./cache/single_flight/{arg}", invalidate_rate = 3600, single_flight = true)]
+async fn expensive_function_single_flight(arg: i32) -> String {
+    SINGLE_FLIGHT_CALLS.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+    "Hello".to_string()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn check_single_flight(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/single_flight/expensive_function_single_flight/150").unwrap_or_default();
+    let handles: Vec<_> = (0..10)
+        .map(|_| tokio::spawn(expensive_function_single_flight(150)))
+        .collect();
+    for handle in handles {
+        assert_eq!(handle.await.unwrap().unwrap(), "Hello");
+    }
+    // only one of the concurrent calls ran the function
+    assert_eq!(SINGLE_FLIGHT_CALLS.load(std::sync::atomic::Ordering::SeqCst), 1);
+}
+
+static SINGLE_FLIGHT_PRIMING_CALLS: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+#[cache_async(cache_root = "./cache/single_flight_priming/{arg}", invalidate_rate = 3600, single_flight = true)]
+async fn expensive_function_single_flight_priming(arg: i32) -> String {
+    SINGLE_FLIGHT_PRIMING_CALLS.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+    "Hello".to_string()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn check_single_flight_prime(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/single_flight_priming/expensive_function_single_flight_priming/155").unwrap_or_default();
+    let primed = tokio::spawn(expensive_function_single_flight_priming_prime(155));
+    let called = tokio::spawn(expensive_function_single_flight_priming(155));
+    primed.await.unwrap().unwrap();
+    assert_eq!(called.await.unwrap().unwrap(), "Hello");
+    // the call waited for the priming one rather than running the function as well
+    assert_eq!(SINGLE_FLIGHT_PRIMING_CALLS.load(std::sync::atomic::Ordering::SeqCst), 1);
+}
+
+#[cache_async(cache_root = "./cache/fallback/{arg}", invalidate_rate = 3600, on_error = "fallback", write_mode = "blocking")]
+async fn expensive_function_fallback(arg: i32) -> String {
+    "Hello".to_string()
+}
+
+#[cache_async(cache_root = "./cache/panic/{arg}", invalidate_rate = 3600, on_error = "panic")]
+async fn expensive_function_panic(arg: i32) -> Result<String, std::fmt::Error> {
+    Ok("Hello".to_string())
+}
+
+#[tokio::test]
+async fn check_on_error_fallback(){
+    // clear the cache
+    let cache_path = "./cache/fallback/expensive_function_fallback/160";
+    std::fs::remove_dir_all(cache_path).unwrap_or_default();
+    // the return type is not wrapped
+    let result1: String = expensive_function_fallback(160).await;
+    assert_eq!(result1, "Hello");
+    // a corrupt cache file is logged and the function runs again
+    std::fs::write(format!("{cache_path}/data.json"), "not json").unwrap();
+    let result2: String = expensive_function_fallback(160).await;
+    assert_eq!(result2, "Hello");
+}
+
+#[tokio::test]
+async fn check_on_error_panic(){
+    // the return type is not wrapped
+    let result: Result<String, std::fmt::Error> = expensive_function_panic(170).await;
+    assert_eq!(result.unwrap(), "Hello");
+}
+
+static CACHE_ERRORS_CALLS: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+#[cache_async(cache_root = "./cache/cache_errors/{arg}", invalidate_rate = 3600, cache_errors = true, error_invalidate_rate = 1, write_mode = "blocking")]
+async fn expensive_function_cache_errors(arg: i32) -> Result<String, String> {
+    CACHE_ERRORS_CALLS.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+    Err("Not found".to_string())
+}
+
+#[tokio::test]
+async fn check_cache_errors(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/cache_errors/expensive_function_cache_errors/180").unwrap_or_default();
+    let result1 = expensive_function_cache_errors(180).await.unwrap();
+    assert_eq!(result1, Err("Not found".to_string()));
+    // the error is served from the cache
+    let result2 = expensive_function_cache_errors(180).await.unwrap();
+    assert_eq!(result2, Err("Not found".to_string()));
+    assert_eq!(CACHE_ERRORS_CALLS.load(std::sync::atomic::Ordering::SeqCst), 1);
+    // until it expires
+    tokio::time::sleep(tokio::time::Duration::from_millis(1100)).await;
+    let result3 = expensive_function_cache_errors(180).await.unwrap();
+    assert_eq!(result3, Err("Not found".to_string()));
+    assert_eq!(CACHE_ERRORS_CALLS.load(std::sync::atomic::Ordering::SeqCst), 2);
+}
+
+#[cache_async(cache_root = "./cache/mtime/{arg}", invalidate_rate = 3600, timestamp = "mtime", write_mode = "blocking")]
+async fn expensive_function_mtime(arg: i32) -> String {
+    "Hello".to_string()
+}
+
+#[tokio::test]
+async fn check_cache_hit_mtime(){
+    // clear the cache
+    let cache_path = "./cache/mtime/expensive_function_mtime/190";
+    std::fs::remove_dir_all(cache_path).unwrap_or_default();
+    let result1 = expensive_function_mtime(190).await.unwrap();
+    assert_eq!(result1, "Hello");
+    // the payload is stored without an envelope
+    assert_eq!(std::fs::read_to_string(format!("{cache_path}/data.json")).unwrap(), "\"Hello\"");
+    std::fs::write(format!("{cache_path}/data.json"), "\"Hello world\"").unwrap();
+    let result2 = expensive_function_mtime(190).await.unwrap();
+    assert_eq!(result2, "Hello world");
+}
+
+#[tokio::test]
+async fn check_embedded_timestamp_expiry(){
+    // clear the cache
+    let cache_path = "./cache/not_result/expensive_function_not_result/200";
+    std::fs::remove_dir_all(cache_path).unwrap_or_default();
+    std::fs::create_dir_all(cache_path).unwrap();
+    // a fresh file whose embedded timestamp is two hours old is expired, regardless of its mtime
+    let two_hours_ago = chrono::Utc::now().timestamp_millis() - 2 * 3600 * 1000;
+    let stale = CacheEnvelope { written_at: two_hours_ago, payload: "Hello world" };
+    std::fs::write(format!("{cache_path}/data.json"), serde_json::to_string(&stale).unwrap()).unwrap();
+    let result = expensive_function_not_result(200).await.unwrap();
+    assert_eq!(result, "Hello");
+}
+
+#[cache_async(cache_root = "./cache/bypass/{arg}", invalidate_rate = 3600, bypass_env = "DISK_CACHE_TEST_BYPASS", write_mode = "blocking")]
+async fn expensive_function_bypass(arg: i32) -> String {
+    "Hello".to_string()
+}
+
+#[tokio::test]
+async fn check_bypass_env(){
+    // clear the cache
+    let cache_path = "./cache/bypass/expensive_function_bypass/210";
+    std::fs::remove_dir_all(cache_path).unwrap_or_default();
+    let result1 = expensive_function_bypass(210).await.unwrap();
+    assert_eq!(result1, "Hello");
+    std::fs::write(format!("{cache_path}/data.json"), serde_json::to_string(&envelope("Hello world")).unwrap()).unwrap();
+    // the cache is ignored while bypassed, and rewritten with the fresh value
+    std::env::set_var("DISK_CACHE_TEST_BYPASS", "1");
+    let result2 = expensive_function_bypass(210).await.unwrap();
+    std::env::remove_var("DISK_CACHE_TEST_BYPASS");
+    assert_eq!(result2, "Hello");
+    let data = std::fs::read_to_string(format!("{cache_path}/data.json")).unwrap();
+    assert_eq!(serde_json::from_str::<CacheEnvelope<String>>(&data).unwrap().payload, "Hello");
+}
+
+#[cache_async(cache_root = "./cache/duration/{arg}", invalidate_rate = "1h", write_mode = "blocking")]
+async fn expensive_function_duration(arg: i32) -> String {
+    "Hello".to_string()
+}
+
+#[tokio::test]
+async fn check_duration_string(){
+    // clear the cache
+    let cache_path = "./cache/duration/expensive_function_duration/220";
+    std::fs::remove_dir_all(cache_path).unwrap_or_default();
+    std::fs::create_dir_all(cache_path).unwrap();
+    // 59 minutes old is still valid with a one hour invalidate rate
+    let fresh = CacheEnvelope { written_at: chrono::Utc::now().timestamp_millis() - 59 * 60 * 1000, payload: "Hello world" };
+    std::fs::write(format!("{cache_path}/data.json"), serde_json::to_string(&fresh).unwrap()).unwrap();
+    assert_eq!(expensive_function_duration(220).await.unwrap(), "Hello world");
+    // 61 minutes old is not
+    let stale = CacheEnvelope { written_at: chrono::Utc::now().timestamp_millis() - 61 * 60 * 1000, payload: "Hello world" };
+    std::fs::write(format!("{cache_path}/data.json"), serde_json::to_string(&stale).unwrap()).unwrap();
+    assert_eq!(expensive_function_duration(220).await.unwrap(), "Hello");
+}
+
+#[cache_async(cache_root = "./cache/gzip/{arg}", invalidate_rate = 3600, compress = "gzip", write_mode = "blocking")]
+async fn expensive_function_gzip(arg: i32) -> String {
+    "Hello".repeat(100)
+}
+
+#[cache_async(cache_root = "./cache/gzip_bincode/{arg}", invalidate_rate = 3600, format = "bincode", compress = "gzip", write_mode = "blocking")]
+async fn expensive_function_gzip_bincode(arg: i32) -> String {
+    "Hello".repeat(100)
+}
+
+#[tokio::test]
+async fn check_gzip(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/gzip/expensive_function_gzip/230").unwrap_or_default();
+    std::fs::remove_dir_all("./cache/gzip_bincode/expensive_function_gzip_bincode/230").unwrap_or_default();
+    assert_eq!(expensive_function_gzip(230).await.unwrap(), "Hello".repeat(100));
+    assert_eq!(expensive_function_gzip_bincode(230).await.unwrap(), "Hello".repeat(100));
+    // the files are compressed
+    let json = std::fs::metadata("./cache/gzip/expensive_function_gzip/230/data.json.gz").unwrap();
+    let bincode = std::fs::metadata("./cache/gzip_bincode/expensive_function_gzip_bincode/230/data.bin.gz").unwrap();
+    assert!(json.len() < 500);
+    assert!(bincode.len() < 500);
+    // and read back on a hit
+    assert_eq!(expensive_function_gzip(230).await.unwrap(), "Hello".repeat(100));
+    assert_eq!(expensive_function_gzip_bincode(230).await.unwrap(), "Hello".repeat(100));
+}
+
+#[cache_async(cache_root = "./cache/memory/{arg}", invalidate_rate = 3600, memory_capacity = 2, write_mode = "blocking")]
+async fn expensive_function_memory(arg: i32) -> String {
+    "Hello".to_string()
+}
+
+#[tokio::test]
+async fn check_memory_cache(){
+    // clear the cache
+    let cache_path = "./cache/memory/expensive_function_memory/240";
+    std::fs::remove_dir_all(cache_path).unwrap_or_default();
+    assert_eq!(expensive_function_memory(240).await.unwrap(), "Hello");
+    // the in-memory entry is served without touching the disk
+    std::fs::write(format!("{cache_path}/data.json"), serde_json::to_string(&envelope("Hello world")).unwrap()).unwrap();
+    assert_eq!(expensive_function_memory(240).await.unwrap(), "Hello");
+    // until it is evicted by newer entries
+    expensive_function_memory(241).await.unwrap();
+    expensive_function_memory(242).await.unwrap();
+    assert_eq!(expensive_function_memory(240).await.unwrap(), "Hello world");
+}
+
+#[cache_async(cache_root = "./cache/clear/{arg}", invalidate_rate = 3600, write_mode = "blocking")]
+async fn expensive_function_clear(arg: i32, other: i32) -> String {
+    format!("Hello {other}")
+}
+
+#[tokio::test]
+async fn check_clear(){
+    // clear the cache
+    let cache_path = "./cache/clear/expensive_function_clear/250";
+    std::fs::remove_dir_all(cache_path).unwrap_or_default();
+    assert_eq!(expensive_function_clear(250, 1).await.unwrap(), "Hello 1");
+    assert_eq!(expensive_function_clear(250, 2).await.unwrap(), "Hello 1");
+    clear_expensive_function_clear(250, 2).await.unwrap();
+    assert!(std::fs::metadata(format!("{cache_path}/data.json")).is_err());
+    assert_eq!(expensive_function_clear(250, 2).await.unwrap(), "Hello 2");
+    // clearing a missing entry is fine
+    clear_expensive_function_clear(251, 0).await.unwrap();
+}
+
+static MEMORY_CLEAR_CALLS: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+#[cache_async(cache_root = "./cache/memory_clear/{arg}", invalidate_rate = 3600, memory_capacity = 4, write_mode = "blocking")]
+async fn expensive_function_memory_clear(arg: i32) -> usize {
+    MEMORY_CLEAR_CALLS.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1
+}
+
+#[tokio::test]
+async fn check_memory_clear(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/memory_clear/expensive_function_memory_clear/255").unwrap_or_default();
+    assert_eq!(expensive_function_memory_clear(255).await.unwrap(), 1);
+    assert_eq!(expensive_function_memory_clear(255).await.unwrap(), 1);
+    // a value still in memory is cached, even once its file is gone
+    std::fs::remove_file(expensive_function_memory_clear_cache_path(255)).unwrap();
+    assert!(expensive_function_memory_clear_is_cached(255).await.unwrap());
+    // clearing the entry forgets it in memory too, so that the next call computes it again
+    clear_expensive_function_memory_clear(255).await.unwrap();
+    assert!(!expensive_function_memory_clear_is_cached(255).await.unwrap());
+    assert_eq!(expensive_function_memory_clear(255).await.unwrap(), 2);
+}
+
+static MEMORY_FLUSH_CALLS: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+#[cache_async(cache_root = "./cache/memory_flush/{arg}", invalidate_rate = 3600, memory_capacity = 4, write_mode = "blocking")]
+async fn expensive_function_memory_flush(arg: i32) -> usize {
+    MEMORY_FLUSH_CALLS.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1
+}
+
+#[tokio::test]
+async fn check_memory_flush(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/memory_flush").unwrap_or_default();
+    assert_eq!(expensive_function_memory_flush(256).await.unwrap(), 1);
+    assert_eq!(expensive_function_memory_flush(256).await.unwrap(), 1);
+    // clearing every entry empties the memory tier too, so that the next call computes again
+    expensive_function_memory_flush_clear_all().await.unwrap();
+    assert_eq!(expensive_function_memory_flush(256).await.unwrap(), 2);
+}
+
+#[tokio::test]
+async fn check_is_cached(){
+    // clear the cache
+    let cache_path = "./cache/blocking/expensive_function_blocking/260";
+    std::fs::remove_dir_all(cache_path).unwrap_or_default();
+    assert!(!expensive_function_blocking_is_cached(260).await.unwrap());
+    expensive_function_blocking(260).await.unwrap();
+    assert!(expensive_function_blocking_is_cached(260).await.unwrap());
+    // an expired entry is not valid
+    let stale = CacheEnvelope { written_at: chrono::Utc::now().timestamp_millis() - 2 * 3600 * 1000, payload: "Hello" };
+    std::fs::write(format!("{cache_path}/data.json"), serde_json::to_string(&stale).unwrap()).unwrap();
+    assert!(!expensive_function_blocking_is_cached(260).await.unwrap());
+}
+
+#[tokio::test]
+async fn check_is_cached_bincode(){
+    // clear the cache
+    let cache_path = "./cache/bincode/expensive_function_bincode/270";
+    std::fs::remove_dir_all(cache_path).unwrap_or_default();
+    std::fs::create_dir_all(cache_path).unwrap();
+    assert!(!expensive_function_bincode_is_cached(270).await.unwrap());
+    std::fs::write(format!("{cache_path}/data.bin"), bincode::serialize(&envelope("Hello")).unwrap()).unwrap();
+    assert!(expensive_function_bincode_is_cached(270).await.unwrap());
+}
+
+#[cache_async(cache_root = "./cache/root_env/{arg}", invalidate_rate = 3600, cache_root_env = "DISK_CACHE_TEST_ROOT", write_mode = "blocking")]
+async fn expensive_function_root_env(arg: i32) -> String {
+    "Hello".to_string()
+}
+
+#[tokio::test]
+async fn check_cache_root_env(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/root_env").unwrap_or_default();
+    std::fs::remove_dir_all("./cache/relocated").unwrap_or_default();
+    std::env::set_var("DISK_CACHE_TEST_ROOT", "./cache/relocated");
+    assert_eq!(expensive_function_root_env(280).await.unwrap(), "Hello");
+    std::env::remove_var("DISK_CACHE_TEST_ROOT");
+    // the prefix before the placeholder was replaced
+    assert!(std::fs::metadata("./cache/relocated/expensive_function_root_env/280/data.json").is_ok());
+    assert!(std::fs::metadata("./cache/root_env/expensive_function_root_env/280/data.json").is_err());
+    // and the compile-time root is used when the variable is unset
+    assert_eq!(expensive_function_root_env(280).await.unwrap(), "Hello");
+    assert!(std::fs::metadata("./cache/root_env/expensive_function_root_env/280/data.json").is_ok());
+}
+
+#[cache_async(cache_root = "./cache/sanitize/{name}", invalidate_rate = 3600, write_mode = "blocking")]
+async fn expensive_function_sanitize(name: String) -> String {
+    format!("Hello {name}")
+}
+
+#[tokio::test]
+async fn check_sanitized_path(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/sanitize").unwrap_or_default();
+    assert_eq!(expensive_function_sanitize("../escaped".to_string()).await.unwrap(), "Hello ../escaped");
+    assert_eq!(expensive_function_sanitize("..".to_string()).await.unwrap(), "Hello ..");
+    // the values stay a single segment inside the cache root
+    assert!(std::fs::metadata("./cache/sanitize/expensive_function_sanitize/..%2Fescaped/data.json").is_ok());
+    assert!(std::fs::metadata("./cache/sanitize/expensive_function_sanitize/%2E%2E/data.json").is_ok());
+    assert!(std::fs::metadata("./cache/escaped").is_err());
+    // values Windows would reject as file names, or too long for one, still form a valid segment
+    let long_name = "x".repeat(400);
+    assert_eq!(expensive_function_sanitize("what? a:b*".to_string()).await.unwrap(), "Hello what? a:b*");
+    assert_eq!(expensive_function_sanitize(long_name.clone()).await.unwrap(), format!("Hello {}", long_name));
+    assert!(std::fs::metadata("./cache/sanitize/expensive_function_sanitize/what%3F a%3Ab%2A/data.json").is_ok());
+    assert!(expensive_function_sanitize_is_cached(long_name.clone()).await.unwrap());
+    assert_eq!(expensive_function_sanitize_cache_path(long_name).parent().unwrap().file_name().unwrap().len(), 255);
+}
+
+#[cache_async(cache_root = "./cache/stale/{arg}", invalidate_rate = 3600, stale_while_revalidate = true)]
+async fn expensive_function_stale(arg: i32) -> Result<String, std::fmt::Error> {
+    Ok("Hello".to_string())
+}
+
+#[tokio::test]
+async fn check_stale_while_revalidate(){
+    // clear the cache
+    let cache_path = "./cache/stale/expensive_function_stale/290";
+    std::fs::remove_dir_all(cache_path).unwrap_or_default();
+    std::fs::create_dir_all(cache_path).unwrap();
+    let stale = CacheEnvelope { written_at: chrono::Utc::now().timestamp_millis() - 2 * 3600 * 1000, payload: "Hello world" };
+    std::fs::write(format!("{cache_path}/data.json"), serde_json::to_string(&stale).unwrap()).unwrap();
+    // the stale value is served right away
+    assert_eq!(expensive_function_stale(290).await.unwrap().unwrap(), "Hello world");
+    // sleep to let the refresh be written
+    tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+    assert_eq!(expensive_function_stale(290).await.unwrap().unwrap(), "Hello");
+}
+
+struct Client {
+    greeting: String,
+}
+
+impl Client {
+    #[cache_async(cache_root = "./cache/method/{arg}", invalidate_rate = 3600, write_mode = "blocking")]
+    async fn expensive_method(&self, arg: i32) -> String {
+        format!("{} {arg}", self.greeting)
+    }
+
+    #[cache_async(cache_root = "./cache/method_key_args", invalidate_rate = 3600, key = "args", write_mode = "blocking")]
+    async fn expensive_method_key_args(&self, arg: i32) -> String {
+        format!("{} {arg}", self.greeting)
+    }
+}
+
+#[tokio::test]
+async fn check_method(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/method/expensive_method/300").unwrap_or_default();
+    std::fs::remove_dir_all("./cache/method_key_args").unwrap_or_default();
+    let client = Client { greeting: "Hello".to_string() };
+    assert_eq!(client.expensive_method(300).await.unwrap(), "Hello 300");
+    assert!(client.expensive_method_is_cached(300).await.unwrap());
+    assert_eq!(client.expensive_method_key_args(300).await.unwrap(), "Hello 300");
+    // the receiver is not part of the key
+    let other = Client { greeting: "Bye".to_string() };
+    assert_eq!(other.expensive_method(300).await.unwrap(), "Hello 300");
+    assert_eq!(other.expensive_method_key_args(300).await.unwrap(), "Hello 300");
+}
+
+#[cache_async(cache_root = "./cache/generic/{arg}", invalidate_rate = 3600, write_mode = "blocking")]
+async fn expensive_function_generic<T>(arg: i32) -> T
+where
+    T: Default,
+{
+    T::default()
+}
+
+#[tokio::test]
+async fn check_generic(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/generic/expensive_function_generic/310").unwrap_or_default();
+    // different instantiations don't share a cache file
+    assert_eq!(expensive_function_generic::<String>(310).await.unwrap(), "");
+    assert_eq!(expensive_function_generic::<i32>(310).await.unwrap(), 0);
+    assert!(expensive_function_generic_is_cached::<i32>(310).await.unwrap());
+    assert!(std::fs::metadata("./cache/generic/expensive_function_generic/310/i32/data.json").is_ok());
+}
+
+#[cache_async(cache_root = "./cache/max_entries/{arg}", invalidate_rate = 3600, write_mode = "blocking", max_entries = 2)]
+async fn expensive_function_max_entries(arg: i32) -> i32 {
+    arg
+}
+
+#[tokio::test]
+async fn check_max_entries(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/max_entries").unwrap_or_default();
+    for arg in 320..324 {
+        expensive_function_max_entries(arg).await.unwrap();
+        // keep the modification times apart
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+    // pruning runs in the background
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    let mut remaining: Vec<String> = std::fs::read_dir("./cache/max_entries/expensive_function_max_entries").unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    remaining.sort();
+    assert_eq!(remaining, vec!["322", "323"]);
+}
+
+#[cache_async(cache_root = "./cache/max_bytes/{arg}", invalidate_rate = 3600, write_mode = "blocking", max_bytes = 1000)]
+async fn expensive_function_max_bytes(arg: i32) -> String {
+    "x".repeat(400 + arg as usize % 10)
+}
+
+#[tokio::test]
+async fn check_max_bytes(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/max_bytes").unwrap_or_default();
+    for arg in 330..334 {
+        expensive_function_max_bytes(arg).await.unwrap();
+        // keep the modification times apart
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+    // pruning runs in the background
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    let mut remaining: Vec<String> = std::fs::read_dir("./cache/max_bytes/expensive_function_max_bytes").unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    remaining.sort();
+    assert_eq!(remaining, vec!["332", "333"]);
+}
+
+#[cache_async(cache_root = "./cache/future/{arg}", invalidate_rate = 3600, write_mode = "blocking")]
+fn expensive_function_future(arg: i32) -> impl std::future::Future<Output = String> {
+    async move { format!("future {}", arg) }
+}
+
+#[tokio::test]
+async fn check_future_returning(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/future/expensive_function_future/350").unwrap_or_default();
+    assert_eq!(expensive_function_future(350).await.unwrap(), "future 350");
+    assert!(expensive_function_future_is_cached(350).await.unwrap());
+    let cached: CacheEnvelope<String> = serde_json::from_str(&std::fs::read_to_string("./cache/future/expensive_function_future/350/data.json").unwrap()).unwrap();
+    assert_eq!(cached.payload, "future 350");
+}
+
+#[cache_async(cache_root = "./cache/ron/{arg}", invalidate_rate = 3600, format = "ron", write_mode = "blocking")]
+async fn expensive_function_ron(arg: i32) -> Result<(String, Option<i32>), tokio::io::Error> {
+    Ok(("Hello".to_string(), Some(arg)))
+}
+
+#[tokio::test]
+async fn check_cache_hit_ron(){
+    // clear the cache
+    let cache_path = "./cache/ron/expensive_function_ron/360";
+    std::fs::remove_dir_all(cache_path).unwrap_or_default();
+    let result1 = expensive_function_ron(360).await.unwrap().unwrap();
+    assert_eq!(result1, ("Hello".to_string(), Some(360)));
+    // the cache file can be edited by hand
+    std::fs::write(format!("{cache_path}/data.ron"), ron::ser::to_string(&envelope(("Hello world", None::<i32>))).unwrap()).unwrap();
+    let result2 = expensive_function_ron(360).await.unwrap().unwrap();
+    assert_eq!(result2, ("Hello world".to_string(), None));
+}
+
+#[cache_async(cache_root = "./cache/unit/{arg}", invalidate_rate = 3600, write_mode = "blocking")]
+#[allow(deprecated)]
+async fn expensive_function_unit(arg: i32) -> () {
+    let _ = arg;
+}
+
+#[tokio::test]
+async fn check_unit_passthrough(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/unit/370").unwrap_or_default();
+    // the signature is unchanged and nothing is written
+    let () = expensive_function_unit(370).await;
+    assert!(std::fs::metadata("./cache/unit/370").is_err());
+}
+
+static WRITE_ERRORS: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+fn count_write_error(_e: std::io::Error) {
+    WRITE_ERRORS.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+}
+
+#[cache_async(cache_root = "./cache/write_error/{arg}", invalidate_rate = 3600, write_mode = "blocking", on_error = "fallback", on_write_error = count_write_error)]
+async fn expensive_function_write_error(arg: i32) -> i32 {
+    arg
+}
+
+#[tokio::test]
+async fn check_on_write_error(){
+    // a file in place of the cache directory makes the write fail
+    std::fs::remove_dir_all("./cache/write_error").unwrap_or_default();
+    std::fs::create_dir_all("./cache/write_error/expensive_function_write_error").unwrap();
+    std::fs::write("./cache/write_error/expensive_function_write_error/380", "").unwrap();
+    assert_eq!(expensive_function_write_error(380).await, 380);
+    assert_eq!(WRITE_ERRORS.load(std::sync::atomic::Ordering::SeqCst), 1);
+}
+
+static TTL_CALLS: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+#[cache_async(cache_root = "./cache/ttl", key = "args", write_mode = "blocking")]
+async fn expensive_function_ttl(arg: i32, #[ttl] ttl: u64) -> i32 {
+    TTL_CALLS.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+    arg
+}
+
+#[tokio::test]
+async fn check_ttl_argument(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/ttl").unwrap_or_default();
+    expensive_function_ttl(390, 3600).await.unwrap();
+    // the ttl is not part of the key, and a long one keeps the entry valid
+    expensive_function_ttl(390, 3000).await.unwrap();
+    assert_eq!(TTL_CALLS.load(std::sync::atomic::Ordering::SeqCst), 1);
+    // a zero ttl always runs the function
+    expensive_function_ttl(390, 0).await.unwrap();
+    assert_eq!(TTL_CALLS.load(std::sync::atomic::Ordering::SeqCst), 2);
+    assert!(!expensive_function_ttl_is_cached(390, 0).await.unwrap());
+    assert!(expensive_function_ttl_is_cached(390, 3600).await.unwrap());
+}
+
+#[cache_async(cache_root = "./cache/namespace/{arg}", invalidate_rate = 3600, write_mode = "blocking", namespace = "v1", flat = true)]
+async fn expensive_function_namespace_v1(arg: i32) -> String {
+    format!("v1 {}", arg)
+}
+
+#[cache_async(cache_root = "./cache/namespace/{arg}", invalidate_rate = 3600, write_mode = "blocking", namespace = "v2", flat = true)]
+async fn expensive_function_namespace_v2(arg: i32) -> String {
+    format!("v2 {}", arg)
+}
+
+#[tokio::test]
+async fn check_namespace(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/namespace").unwrap_or_default();
+    assert_eq!(expensive_function_namespace_v1(400).await.unwrap(), "v1 400");
+    // the namespace comes before the interpolated argument, so bumping it misses the old entry
+    assert_eq!(expensive_function_namespace_v2(400).await.unwrap(), "v2 400");
+    assert!(std::fs::metadata("./cache/namespace/v1/400/data.json").is_ok());
+    assert!(std::fs::metadata("./cache/namespace/v2/400/data.json").is_ok());
+}
+
+static PRIME_CALLS: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+#[cache_async(cache_root = "./cache/prime/{arg}", invalidate_rate = 3600)]
+async fn expensive_function_prime(arg: i32) -> Result<i32, String> {
+    PRIME_CALLS.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+    Ok(arg)
+}
+
+#[tokio::test]
+async fn check_prime(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/prime/expensive_function_prime/410").unwrap_or_default();
+    expensive_function_prime_prime(410).await.unwrap();
+    // the entry is written before priming returns, and priming again is a no-op
+    assert!(expensive_function_prime_is_cached(410).await.unwrap());
+    expensive_function_prime_prime(410).await.unwrap();
+    assert_eq!(expensive_function_prime(410).await.unwrap(), Ok(410));
+    assert_eq!(PRIME_CALLS.load(std::sync::atomic::Ordering::SeqCst), 1);
+}
+
+#[cache_async(cache_root = "./cache/messagepack/{arg}", invalidate_rate = 3600, format = "messagepack", write_mode = "blocking")]
+async fn expensive_function_messagepack(arg: i32) -> Result<Vec<i32>, tokio::io::Error> {
+    Ok(vec![arg])
+}
+
+#[tokio::test]
+async fn check_cache_hit_messagepack(){
+    // clear the cache
+    let cache_path = "./cache/messagepack/expensive_function_messagepack/420";
+    std::fs::remove_dir_all(cache_path).unwrap_or_default();
+    let result1 = expensive_function_messagepack(420).await.unwrap().unwrap();
+    assert_eq!(result1, vec![420]);
+    // only the Ok value is cached
+    std::fs::write(format!("{cache_path}/data.msgpack"), rmp_serde::to_vec(&envelope(vec![1, 2])).unwrap()).unwrap();
+    let result2 = expensive_function_messagepack(420).await.unwrap().unwrap();
+    assert_eq!(result2, vec![1, 2]);
+}
+
+#[cache_async(cache_root = "./cache/zstd/{arg}", invalidate_rate = 3600, compress = "zstd", compress_level = 19, write_mode = "blocking")]
+async fn expensive_function_zstd(arg: i32) -> String {
+    "Hello".repeat(100)
+}
+
+#[tokio::test]
+async fn check_zstd(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/zstd/expensive_function_zstd/450").unwrap_or_default();
+    assert_eq!(expensive_function_zstd(450).await.unwrap(), "Hello".repeat(100));
+    // the file is compressed
+    let data = std::fs::read("./cache/zstd/expensive_function_zstd/450/data.json.zst").unwrap();
+    assert!(data.len() < 500);
+    let cached: CacheEnvelope<String> = serde_json::from_slice(&zstd::decode_all(data.as_slice()).unwrap()).unwrap();
+    assert_eq!(cached.payload, "Hello".repeat(100));
+    // and read back on a hit
+    assert_eq!(expensive_function_zstd(450).await.unwrap(), "Hello".repeat(100));
+}
+
+static OPTION_CALLS: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+#[cache_async(cache_root = "./cache/option/{arg}", invalidate_rate = 3600, write_mode = "blocking")]
+async fn expensive_function_option(arg: i32) -> Option<String> {
+    OPTION_CALLS.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+    (arg % 2 == 0).then(|| format!("even {}", arg))
+}
+
+#[tokio::test]
+async fn check_option(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/option/expensive_function_option/460").unwrap_or_default();
+    std::fs::remove_dir_all("./cache/option/expensive_function_option/461").unwrap_or_default();
+    assert_eq!(expensive_function_option(460).await.unwrap(), Some("even 460".to_string()));
+    assert_eq!(expensive_function_option(461).await.unwrap(), None);
+    // `None` is cached too
+    assert_eq!(expensive_function_option(461).await.unwrap(), None);
+    assert_eq!(OPTION_CALLS.load(std::sync::atomic::Ordering::SeqCst), 2);
+    let cached: CacheEnvelope<Option<String>> = serde_json::from_str(&std::fs::read_to_string("./cache/option/expensive_function_option/461/data.json").unwrap()).unwrap();
+    assert_eq!(cached.payload, None);
+}
+
+/// A type without serde support, stored with its own encoding.
+#[derive(Debug, PartialEq)]
+struct Point {
+    x: u16,
+    y: u16,
+}
+
+fn encode_point(point: &Point) -> Vec<u8> {
+    [point.x.to_le_bytes(), point.y.to_le_bytes()].concat()
+}
+
+fn decode_point(data: &[u8]) -> Result<Point, String> {
+    match data {
+        [x0, x1, y0, y1] => Ok(Point { x: u16::from_le_bytes([*x0, *x1]), y: u16::from_le_bytes([*y0, *y1]) }),
+        _ => Err(format!("expected 4 bytes, found {}", data.len())),
+    }
+}
+
+#[cache(cache_root = "./cache/custom/{arg}", serialize_with = encode_point, deserialize_with = decode_point, write_mode = "blocking")]
+fn expensive_function_custom(arg: u16) -> Point {
+    Point { x: arg, y: arg + 1 }
+}
+
+#[test]
+fn check_custom_serializer(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/custom/expensive_function_custom/470").unwrap_or_default();
+    assert_eq!(expensive_function_custom(470).unwrap(), Point { x: 470, y: 471 });
+    // the write time comes first, then the bytes of the custom encoding
+    let data = std::fs::read("./cache/custom/expensive_function_custom/470/data.custom").unwrap();
+    assert_eq!(&data[8..], encode_point(&Point { x: 470, y: 471 }).as_slice());
+    assert_eq!(expensive_function_custom(470).unwrap(), Point { x: 470, y: 471 });
+    assert!(expensive_function_custom_is_cached(470).unwrap());
+}
+
+/// The envelope of an entry written with `invalidate_jitter`.
+#[derive(serde::Deserialize)]
+struct JitteredEnvelope {
+    jitter: i64,
+}
+
+#[cache_async(cache_root = "./cache/jitter/{arg}", invalidate_rate = 0, invalidate_jitter = 1000, write_mode = "blocking")]
+async fn expensive_function_jitter(arg: i32) -> String {
+    format!("jittered {}", arg)
+}
+
+#[tokio::test]
+async fn check_invalidate_jitter(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/jitter/expensive_function_jitter/480").unwrap_or_default();
+    assert_eq!(expensive_function_jitter(480).await.unwrap(), "jittered 480");
+    let cached: JitteredEnvelope = serde_json::from_str(&std::fs::read_to_string("./cache/jitter/expensive_function_jitter/480/data.json").unwrap()).unwrap();
+    assert!((0..=1000).contains(&cached.jitter));
+    // with no invalidate rate of its own, the entry only lives for its jitter
+    assert_eq!(expensive_function_jitter_is_cached(480).await.unwrap(), cached.jitter > 0);
+}
+
+#[cache_async(cache_root = "./cache/corrupt/{arg}", invalidate_rate = 3600, write_mode = "blocking")]
+async fn expensive_function_corrupt(arg: i32) -> String {
+    format!("fresh {}", arg)
+}
+
+#[tokio::test]
+async fn check_corrupt_entry(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/corrupt/expensive_function_corrupt/490").unwrap_or_default();
+    assert_eq!(expensive_function_corrupt(490).await.unwrap(), "fresh 490");
+    // a corrupt entry is a miss, and is rewritten
+    std::fs::write("./cache/corrupt/expensive_function_corrupt/490/data.json", "not json").unwrap();
+    assert!(!expensive_function_corrupt_is_cached(490).await.unwrap());
+    assert_eq!(expensive_function_corrupt(490).await.unwrap(), "fresh 490");
+    let cached: CacheEnvelope<String> = serde_json::from_str(&std::fs::read_to_string("./cache/corrupt/expensive_function_corrupt/490/data.json").unwrap()).unwrap();
+    assert_eq!(cached.payload, "fresh 490");
+}
+
+/// A plain serde struct, whose json errors must convert into the cache error without any `From` impl of its own.
+#[derive(Debug, PartialEq, Clone, serde::Serialize, serde::Deserialize)]
+struct Report {
+    id: u32,
+    lines: Vec<String>,
+}
+
+#[cache(cache_root = "./cache/struct/{arg}", invalidate_rate = 3600, write_mode = "blocking")]
+fn expensive_function_struct(arg: u32) -> Report {
+    Report { id: arg, lines: vec![format!("line {}", arg)] }
+}
+
+#[test]
+fn check_struct_return(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/struct/expensive_function_struct/500").unwrap_or_default();
+    let expected = Report { id: 500, lines: vec!["line 500".to_string()] };
+    assert_eq!(expensive_function_struct(500).unwrap(), expected);
+    let cached: CacheEnvelope<Report> = serde_json::from_str(&std::fs::read_to_string("./cache/struct/expensive_function_struct/500/data.json").unwrap()).unwrap();
+    assert_eq!(cached.payload, expected);
+    assert_eq!(expensive_function_struct(500).unwrap(), expected);
+}
+
+#[cache_async(cache_root = "./cache/cow/{arg}", invalidate_rate = 3600, write_mode = "blocking")]
+async fn expensive_function_cow(arg: i32) -> std::borrow::Cow<'static, str> {
+    if arg == 0 {
+        std::borrow::Cow::Borrowed("zero")
+    } else {
+        std::borrow::Cow::Owned(format!("cow {}", arg))
+    }
+}
+
+#[tokio::test]
+async fn check_cow_return(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/cow/expensive_function_cow/510").unwrap_or_default();
+    assert_eq!(expensive_function_cow(510).await.unwrap(), "cow 510");
+    // the cached value comes back owned
+    let cached = expensive_function_cow(510).await.unwrap();
+    assert!(matches!(cached, std::borrow::Cow::Owned(_)));
+    assert_eq!(cached, "cow 510");
+}
+
+#[cache(cache_root = "./cache/arc/{arg}", invalidate_rate = 3600, write_mode = "blocking", memory_capacity = 4)]
+fn expensive_function_arc(arg: u32) -> std::sync::Arc<Report> {
+    std::sync::Arc::new(Report { id: arg, lines: Vec::new() })
+}
+
+#[test]
+fn check_arc_return(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/arc/expensive_function_arc/520").unwrap_or_default();
+    assert_eq!(*expensive_function_arc(520).unwrap(), Report { id: 520, lines: Vec::new() });
+    assert_eq!(*expensive_function_arc(520).unwrap(), Report { id: 520, lines: Vec::new() });
+    assert!(expensive_function_arc_is_cached(520).unwrap());
+}
+
+#[cache(cache_root = "./cache/array/{arg}", invalidate_rate = 3600, write_mode = "blocking")]
+fn expensive_function_array(arg: u8) -> ([u8; 3], Box<[u16]>) {
+    ([arg, arg, arg], vec![arg as u16; 2].into_boxed_slice())
+}
+
+#[test]
+fn check_array_return(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/array/expensive_function_array/53").unwrap_or_default();
+    assert_eq!(expensive_function_array(53).unwrap(), ([53, 53, 53], vec![53, 53].into_boxed_slice()));
+    assert_eq!(expensive_function_array(53).unwrap(), ([53, 53, 53], vec![53, 53].into_boxed_slice()));
+}
+
+#[cache_async(cache_root = "./cache/read_only/{arg}", invalidate_rate = 3600, read_only = true)]
+async fn expensive_function_read_only(arg: i32) -> String {
+    format!("computed {}", arg)
+}
+
+#[tokio::test]
+async fn check_read_only(){
+    // an existing entry is served
+    std::fs::create_dir_all("./cache/read_only/expensive_function_read_only/540").unwrap();
+    std::fs::write("./cache/read_only/expensive_function_read_only/540/data.json", serde_json::to_string(&envelope("prebuilt")).unwrap()).unwrap();
+    assert_eq!(expensive_function_read_only(540).await.unwrap(), "prebuilt");
+    // a miss runs the function without writing anything
+    std::fs::remove_dir_all("./cache/read_only/expensive_function_read_only/541").unwrap_or_default();
+    assert_eq!(expensive_function_read_only(541).await.unwrap(), "computed 541");
+    expensive_function_read_only_prime(541).await.unwrap();
+    tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
+    assert!(std::fs::metadata("./cache/read_only/expensive_function_read_only/541").is_err());
+}
+
+#[cache_async(cache_root = "./cache/toml/{arg}", invalidate_rate = 3600, format = "toml", write_mode = "blocking")]
+async fn expensive_function_toml(arg: u32) -> Report {
+    Report { id: arg, lines: vec!["toml".to_string()] }
+}
+
+#[tokio::test]
+async fn check_cache_hit_toml(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/toml/expensive_function_toml/550").unwrap_or_default();
+    assert_eq!(expensive_function_toml(550).await.unwrap(), Report { id: 550, lines: vec!["toml".to_string()] });
+    // modify the file and make sure the cache is hit
+    let edited = envelope(Report { id: 551, lines: Vec::new() });
+    std::fs::write("./cache/toml/expensive_function_toml/550/data.toml", toml::to_string(&edited).unwrap()).unwrap();
+    assert_eq!(expensive_function_toml(550).await.unwrap(), Report { id: 551, lines: Vec::new() });
+}
+
+#[cache(cache_root = "./cache/yaml/{arg}", invalidate_rate = 3600, format = "yaml", write_mode = "blocking")]
+fn expensive_function_yaml(arg: i32) -> Option<Vec<i32>> {
+    (arg > 0).then(|| vec![arg])
+}
+
+#[test]
+fn check_cache_hit_yaml(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/yaml/expensive_function_yaml/560").unwrap_or_default();
+    assert_eq!(expensive_function_yaml(560).unwrap(), Some(vec![560]));
+    let cached: CacheEnvelope<Option<Vec<i32>>> = serde_yaml::from_str(&std::fs::read_to_string("./cache/yaml/expensive_function_yaml/560/data.yaml").unwrap()).unwrap();
+    assert_eq!(cached.payload, Some(vec![560]));
+    // modify the file and make sure the cache is hit
+    std::fs::write("./cache/yaml/expensive_function_yaml/560/data.yaml", serde_yaml::to_string(&envelope(Some(vec![1, 2]))).unwrap()).unwrap();
+    assert_eq!(expensive_function_yaml(560).unwrap(), Some(vec![1, 2]));
+}
+
+#[cache(cache_root = "./cache/fields/{report.id}-{query}/{query.len()}", invalidate_rate = 3600, write_mode = "blocking")]
+fn expensive_function_fields(report: &Report, query: &str) -> String {
+    format!("{} {}", report.id, query)
+}
+
+#[test]
+fn check_field_placeholders(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/fields/expensive_function_fields/570-abc").unwrap_or_default();
+    let report = Report { id: 570, lines: Vec::new() };
+    assert_eq!(expensive_function_fields(&report, "abc").unwrap(), "570 abc");
+    assert!(std::fs::metadata("./cache/fields/expensive_function_fields/570-abc/3/data.json").is_ok());
+}
+
+static REFRESH_AHEAD_CALLS: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+#[cache_async(cache_root = "./cache/refresh_ahead/{arg}", invalidate_rate = 3600, refresh_ahead = "1h", write_mode = "blocking")]
+async fn expensive_function_refresh_ahead(arg: i32) -> i32 {
+    REFRESH_AHEAD_CALLS.fetch_add(1, std::sync::atomic::Ordering::SeqCst) as i32 + arg
+}
+
+#[tokio::test]
+async fn check_refresh_ahead(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/refresh_ahead/expensive_function_refresh_ahead/580").unwrap_or_default();
+    assert_eq!(expensive_function_refresh_ahead(580).await.unwrap(), 580);
+    // every entry is within the window, so a hit is served and refreshed in the background
+    assert_eq!(expensive_function_refresh_ahead(580).await.unwrap(), 580);
+    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+    assert_eq!(REFRESH_AHEAD_CALLS.load(std::sync::atomic::Ordering::SeqCst), 2);
+    assert_eq!(expensive_function_refresh_ahead(580).await.unwrap(), 581);
+}
+
+/// The error type of functions cached with `cache_fallible`.
+#[derive(Debug)]
+enum FetchError {
+    Io(std::io::Error),
+    NotFound,
+}
+
+impl From<std::io::Error> for FetchError {
+    fn from(e: std::io::Error) -> Self {
+        FetchError::Io(e)
+    }
+}
+
+#[cache_fallible(cache_root = "./cache/fallible/{arg}", invalidate_rate = 3600, write_mode = "blocking")]
+async fn expensive_function_fallible(arg: i32) -> Result<String, FetchError> {
+    if arg < 0 {
+        return Err(FetchError::NotFound);
+    }
+    Ok(format!("found {}", arg))
+}
+
+#[cache_fallible(cache_root = "./cache/fallible_sync/{arg}", invalidate_rate = 3600)]
+fn expensive_function_fallible_sync(arg: u8) -> Result<std::collections::HashMap<(u8, u8), u8>, FetchError> {
+    Ok(std::collections::HashMap::from([((arg, arg), arg)]))
+}
+
+#[tokio::test]
+async fn check_fallible(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/fallible/expensive_function_fallible/590").unwrap_or_default();
+    // the signature is kept as it is
+    assert_eq!(expensive_function_fallible(590).await.unwrap(), "found 590");
+    assert!(expensive_function_fallible_is_cached(590).await.unwrap());
+    assert!(matches!(expensive_function_fallible(-1).await, Err(FetchError::NotFound)));
+    // json maps need string keys, so the cache error comes back as the function's own
+    match expensive_function_fallible_sync(59) {
+        Err(FetchError::Io(e)) => assert_eq!(e.kind(), std::io::ErrorKind::InvalidData),
+        other => panic!("expected a serialization error, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn check_cache_path(){
+    assert_eq!(expensive_function_corrupt_cache_path(600), std::path::PathBuf::from("./cache/corrupt/expensive_function_corrupt/600/data.json"));
+    assert_eq!(expensive_function_zstd_cache_path(600), std::path::PathBuf::from("./cache/zstd/expensive_function_zstd/600/data.json.zst"));
+    // the path is the one the function writes to
+    let path = expensive_function_key_args_cache_path(600, "b".to_string());
+    std::fs::remove_file(&path).unwrap_or_default();
+    expensive_function_key_args(600, "b".to_string()).await.unwrap();
+    tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
+    assert!(path.starts_with("./cache/key_args"));
+    assert!(std::fs::metadata(&path).is_ok());
+}
+
+#[cache_async(cache_root = "./cache/tuple/{arg}", invalidate_rate = 3600, write_mode = "blocking", format = "bincode")]
+async fn expensive_function_tuple(arg: u32) -> (Report, Vec<Report>) {
+    (Report { id: arg, lines: Vec::new() }, vec![Report { id: arg + 1, lines: vec!["record".to_string()] }])
+}
+
+#[cache(cache_root = "./cache/tuple_result/{arg}", invalidate_rate = 3600, write_mode = "blocking")]
+fn expensive_function_tuple_result(arg: u8) -> Result<([u8; 2], (u8,)), String> {
+    if arg == 0 {
+        return Err("zero".to_string());
+    }
+    Ok(([arg; 2], (arg,)))
+}
+
+#[tokio::test]
+async fn check_tuple_return(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/tuple/expensive_function_tuple/610").unwrap_or_default();
+    let expected = (Report { id: 610, lines: Vec::new() }, vec![Report { id: 611, lines: vec!["record".to_string()] }]);
+    assert_eq!(expensive_function_tuple(610).await.unwrap(), expected);
+    assert!(expensive_function_tuple_is_cached(610).await.unwrap());
+    assert_eq!(expensive_function_tuple(610).await.unwrap(), expected);
+    // a tuple in a Result is still only cached when it is Ok
+    std::fs::remove_dir_all("./cache/tuple_result/expensive_function_tuple_result/61").unwrap_or_default();
+    assert_eq!(expensive_function_tuple_result(61).unwrap(), Ok(([61; 2], (61,))));
+    let cached: CacheEnvelope<([u8; 2], (u8,))> = serde_json::from_str(&std::fs::read_to_string("./cache/tuple_result/expensive_function_tuple_result/61/data.json").unwrap()).unwrap();
+    assert_eq!(cached.payload, ([61; 2], (61,)));
+    assert_eq!(expensive_function_tuple_result(0).unwrap(), Err("zero".to_string()));
+    assert!(!expensive_function_tuple_result_is_cached(0).unwrap());
+}
+
+fn is_not_empty(values: &[i32]) -> bool {
+    !values.is_empty()
+}
+
+#[cache_async(cache_root = "./cache/cache_if/{arg}", invalidate_rate = 3600, write_mode = "blocking", cache_if = is_not_empty)]
+async fn expensive_function_cache_if(arg: i32) -> Result<Vec<i32>, String> {
+    Ok((0..arg).collect())
+}
+
+#[tokio::test]
+async fn check_cache_if(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/cache_if/expensive_function_cache_if/0").unwrap_or_default();
+    std::fs::remove_dir_all("./cache/cache_if/expensive_function_cache_if/3").unwrap_or_default();
+    // an empty result is returned, but not cached
+    assert_eq!(expensive_function_cache_if(0).await.unwrap(), Ok(Vec::new()));
+    assert!(!expensive_function_cache_if_is_cached(0).await.unwrap());
+    expensive_function_cache_if_prime(0).await.unwrap();
+    assert!(!expensive_function_cache_if_is_cached(0).await.unwrap());
+    assert_eq!(expensive_function_cache_if(3).await.unwrap(), Ok(vec![0, 1, 2]));
+    assert!(expensive_function_cache_if_is_cached(3).await.unwrap());
+}
+
+#[cache_async(cache_root = "./cache/touch/{arg}", invalidate_rate = 3600, write_mode = "blocking", max_entries = 10, touch_on_hit = true)]
+async fn expensive_function_touch(arg: i32) -> i32 {
+    arg
+}
+
+#[tokio::test]
+async fn check_touch_on_hit(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/touch/expensive_function_touch/620").unwrap_or_default();
+    assert_eq!(expensive_function_touch(620).await.unwrap(), 620);
+    // age the entry, which stays valid as its write time is embedded
+    let old = std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_000_000);
+    std::fs::File::options().write(true).open("./cache/touch/expensive_function_touch/620/data.json").unwrap().set_modified(old).unwrap();
+    std::fs::File::open("./cache/touch/expensive_function_touch/620").unwrap().set_modified(old).unwrap();
+    assert_eq!(expensive_function_touch(620).await.unwrap(), 620);
+    tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
+    assert!(std::fs::metadata("./cache/touch/expensive_function_touch/620/data.json").unwrap().modified().unwrap() > old);
+    assert!(std::fs::metadata("./cache/touch/expensive_function_touch/620").unwrap().modified().unwrap() > old);
+}
+
+#[cache_async(cache_root = "./cache/ref_args/{name}", invalidate_rate = 3600)]
+async fn expensive_function_ref_path(name: &str) -> String {
+    format!("path {}", name)
+}
+
+#[cache_async(cache_root = "./cache/ref_args_key", invalidate_rate = 3600, key = "args", write_mode = "blocking")]
+async fn expensive_function_ref_key(name: &str, ids: &[u32], #[ttl] ttl: &std::time::Duration) -> String {
+    format!("key {} {:?} {}", name, ids, ttl.as_secs())
+}
+
+#[tokio::test]
+async fn check_reference_args(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/ref_args/expensive_function_ref_path/630").unwrap_or_default();
+    std::fs::remove_dir_all("./cache/ref_args_key").unwrap_or_default();
+    let name = String::from("630");
+    assert_eq!(expensive_function_ref_path(&name).await.unwrap(), "path 630");
+    let ttl = std::time::Duration::from_secs(60);
+    assert_eq!(expensive_function_ref_key("a", &[1, 2], &ttl).await.unwrap(), "key a [1, 2] 60");
+    // references hash like the values they point to
+    assert!(expensive_function_ref_key_is_cached("a", &(1..3).collect::<Vec<u32>>(), &ttl).await.unwrap());
+    assert!(!expensive_function_ref_key_is_cached("a", &[1], &ttl).await.unwrap());
+}
+
+#[cache(cache_root = "./cache/cache_id/{id}", invalidate_rate = 3600, write_mode = "blocking", cache_id = "user", namespace = "v1")]
+fn get_user(id: u32) -> String {
+    format!("user {}", id)
+}
+
+#[cache(cache_root = "./cache/cache_id/{id}", invalidate_rate = 3600, write_mode = "blocking", cache_id = "user", namespace = "v1")]
+fn get_user_v2(id: u32) -> String {
+    format!("user v2 {}", id)
+}
+
+#[cache(cache_root = "./cache/cache_id/{id}", invalidate_rate = 3600, write_mode = "blocking", cache_id = "team", namespace = "v1")]
+fn get_team(id: u32) -> String {
+    format!("team {}", id)
+}
+
+#[test]
+fn check_cache_id(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/cache_id").unwrap_or_default();
+    assert_eq!(get_user_cache_path(640), std::path::PathBuf::from("./cache/cache_id/user/v1/640/data.json"));
+    assert_eq!(get_user(640).unwrap(), "user 640");
+    // functions with the same id share their entries, and others do not
+    assert_eq!(get_user_v2(640).unwrap(), "user 640");
+    assert_eq!(get_team(640).unwrap(), "team 640");
+}
+
+#[cache(cache_root = "./cache/shared_root/{id}", invalidate_rate = 3600, write_mode = "blocking")]
+fn get_user_name(id: u32) -> String {
+    format!("user {}", id)
+}
+
+#[cache(cache_root = "./cache/shared_root/{id}", invalidate_rate = 3600, write_mode = "blocking")]
+fn get_team_name(id: u32) -> String {
+    format!("team {}", id)
+}
+
+#[test]
+fn check_function_name_in_path(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/shared_root").unwrap_or_default();
+    // functions sharing a cache root do not overwrite each other's entries
+    assert_eq!(get_user_name(650).unwrap(), "user 650");
+    assert_eq!(get_team_name(650).unwrap(), "team 650");
+    assert_eq!(get_user_name(650).unwrap(), "user 650");
+    assert_eq!(get_team_name_cache_path(650), std::path::PathBuf::from("./cache/shared_root/get_team_name/650/data.json"));
+}
+
+#[cache_async(cache_root = "./cache/raw/{arg}", invalidate_rate = 3600, write_mode = "blocking")]
+async fn expensive_function_raw(arg: u8) -> Result<Vec<u8>, std::io::Error> {
+    Ok(vec![arg; 1000])
+}
+
+#[tokio::test]
+async fn check_raw_bytes(){
+    // clear the cache
+    let cache_path = "./cache/raw/expensive_function_raw/66";
+    std::fs::remove_dir_all(cache_path).unwrap_or_default();
+    assert_eq!(expensive_function_raw(66).await.unwrap().unwrap(), vec![66; 1000]);
+    // the bytes follow the write time, without any encoding
+    let data = std::fs::read(format!("{cache_path}/data.raw")).unwrap();
+    assert_eq!(data.len(), 8 + 1000);
+    assert_eq!(&data[8..], &[66; 1000][..]);
+    assert!(expensive_function_raw_is_cached(66).await.unwrap());
+    // modify the file and make sure the cache is hit
+    let mut edited = data[..8].to_vec();
+    edited.extend_from_slice(b"edited");
+    std::fs::write(format!("{cache_path}/data.raw"), edited).unwrap();
+    assert_eq!(expensive_function_raw(66).await.unwrap().unwrap(), b"edited");
+}
+
+#[cache_async(cache_root = "./cache/file_name", file_name = "{arg}.json", invalidate_rate = 3600, write_mode = "blocking")]
+async fn expensive_function_file_name(arg: i32) -> String {
+    format!("Hello {}", arg)
+}
+
+#[tokio::test]
+async fn check_file_name(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/file_name").unwrap_or_default();
+    assert_eq!(expensive_function_file_name(670).await.unwrap(), "Hello 670");
+    assert_eq!(expensive_function_file_name(671).await.unwrap(), "Hello 671");
+    // the entries are files next to each other
+    let mut entries: Vec<String> = std::fs::read_dir("./cache/file_name/expensive_function_file_name").unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    entries.sort();
+    assert_eq!(entries, vec!["670.json", "671.json"]);
+    // and are read back on a hit
+    std::fs::write("./cache/file_name/expensive_function_file_name/670.json", serde_json::to_string(&envelope("Hello world")).unwrap()).unwrap();
+    assert_eq!(expensive_function_file_name(670).await.unwrap(), "Hello world");
+}
+
+#[cache_async(cache_root = "./cache/purge_file_name", file_name = "{arg}.json", invalidate_rate = 3600, write_mode = "blocking")]
+async fn expensive_function_purge_file_name(arg: i32) -> String {
+    format!("Hello {}", arg)
+}
+
+#[tokio::test]
+async fn check_purge_file_name(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/purge_file_name").unwrap_or_default();
+    expensive_function_purge_file_name(672).await.unwrap();
+    let old = CacheEnvelope { written_at: chrono::Utc::now().timestamp_millis() - 2 * 3600 * 1000, payload: "Hello world" };
+    std::fs::write("./cache/purge_file_name/expensive_function_purge_file_name/673.json", serde_json::to_string(&old).unwrap()).unwrap();
+    let root = std::path::Path::new("./cache/purge_file_name");
+    let ttl = std::time::Duration::from_secs(3600);
+    // the entries aren't named `data.*`
+    assert_eq!(disk_cache::purge_expired(root, ttl, false).await.unwrap(), 0);
+    assert_eq!(disk_cache::purge_expired(root, ttl, true).await.unwrap(), 1);
+    assert!(!expensive_function_purge_file_name_is_cached(673).await.unwrap());
+    assert!(expensive_function_purge_file_name_is_cached(672).await.unwrap());
+}
+
+#[tokio::test]
+async fn check_cached_function(){
+    // clear the cache
+    let cache_path = "./cache/cached_function/680";
+    std::fs::remove_dir_all(cache_path).unwrap_or_default();
+    let ttl = std::time::Duration::from_secs(3600);
+    let result1: String = disk_cache::cached(cache_path, ttl, || async { "Hello".to_string() }).await.unwrap();
+    assert_eq!(result1, "Hello");
+    // the entry is written like those of the attribute macros
+    let cached: CacheEnvelope<String> = serde_json::from_str(&std::fs::read_to_string(format!("{cache_path}/data.json")).unwrap()).unwrap();
+    assert_eq!(cached.payload, "Hello");
+    let result2: String = disk_cache::cached(cache_path, ttl, || async { "Hello world".to_string() }).await.unwrap();
+    assert_eq!(result2, "Hello");
+    // and expires after the ttl
+    let result3: String = disk_cache::cached(cache_path, std::time::Duration::ZERO, || async { "Hello world".to_string() }).await.unwrap();
+    assert_eq!(result3, "Hello world");
+}
+
+#[cache_async(cache_root = "./cache/compress_min_bytes/{arg}", invalidate_rate = 3600, compress = "gzip", compress_min_bytes = 200, write_mode = "blocking")]
+async fn expensive_function_compress_min_bytes(arg: usize) -> String {
+    "Hello".repeat(arg)
+}
+
+#[tokio::test]
+async fn check_compress_min_bytes(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/compress_min_bytes").unwrap_or_default();
+    assert_eq!(expensive_function_compress_min_bytes(1).await.unwrap(), "Hello");
+    assert_eq!(expensive_function_compress_min_bytes(100).await.unwrap(), "Hello".repeat(100));
+    // small entries are flagged as stored as they are, and large ones as compressed
+    let small = std::fs::read("./cache/compress_min_bytes/expensive_function_compress_min_bytes/1/data.json.gz").unwrap();
+    let large = std::fs::read("./cache/compress_min_bytes/expensive_function_compress_min_bytes/100/data.json.gz").unwrap();
+    assert_eq!(small[0], 0);
+    assert_eq!(serde_json::from_slice::<CacheEnvelope<String>>(&small[1..]).unwrap().payload, "Hello");
+    assert_eq!(large[0], 1);
+    assert!(large.len() < 200);
+    // and both are read back on a hit
+    assert!(expensive_function_compress_min_bytes_is_cached(1).await.unwrap());
+    assert!(expensive_function_compress_min_bytes_is_cached(100).await.unwrap());
+    assert_eq!(expensive_function_compress_min_bytes(100).await.unwrap(), "Hello".repeat(100));
+}
+
+#[cache_async(cache_root = "./cache/with_meta/{arg}", invalidate_rate = 3600, write_mode = "blocking")]
+async fn expensive_function_with_meta(arg: i32) -> Result<String, String> {
+    if arg < 0 {
+        return Err("negative".to_string());
+    }
+    Ok(format!("Hello {}", arg))
+}
+
+#[tokio::test]
+async fn check_with_meta(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/with_meta").unwrap_or_default();
+    let (result1, meta1) = expensive_function_with_meta_with_meta(690).await.unwrap();
+    assert_eq!(result1, Ok("Hello 690".to_string()));
+    assert!(!meta1.hit);
+    assert_eq!(meta1.age, std::time::Duration::ZERO);
+    assert_eq!(meta1.path, expensive_function_with_meta_cache_path(690));
+    tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+    let (result2, meta2) = expensive_function_with_meta_with_meta(690).await.unwrap();
+    assert_eq!(result2, Ok("Hello 690".to_string()));
+    assert!(meta2.hit);
+    assert!(meta2.age >= std::time::Duration::from_millis(100));
+    // errors of the function are returned as they are, and not cached
+    let (result3, meta3) = expensive_function_with_meta_with_meta(-1).await.unwrap();
+    assert_eq!(result3, Err("negative".to_string()));
+    assert!(!meta3.hit);
+    assert!(!expensive_function_with_meta_is_cached(-1).await.unwrap());
+}
+
+static COLD_WRITE_ERRORS: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+fn count_cold_write_error(_e: std::io::Error) {
+    COLD_WRITE_ERRORS.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+}
+
+#[cache_async(cache_root = "./cache/cold/{arg}/nested", invalidate_rate = 3600, write_mode = "blocking", on_write_error = count_cold_write_error)]
+async fn expensive_function_cold(arg: i32) -> String {
+    format!("Hello {}", arg)
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+async fn check_concurrent_cold_calls(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/cold").unwrap_or_default();
+    // every call sees the directories missing, and creates them at the same time
+    let handles: Vec<_> = (0..32)
+        .map(|_| tokio::spawn(expensive_function_cold(700)))
+        .collect();
+    for handle in handles {
+        assert_eq!(handle.await.unwrap().unwrap(), "Hello 700");
+    }
+    // none of the writes failed, and no temp file is left behind
+    assert_eq!(COLD_WRITE_ERRORS.load(std::sync::atomic::Ordering::SeqCst), 0);
+    assert!(expensive_function_cold_is_cached(700).await.unwrap());
+    let entries = std::fs::read_dir("./cache/cold/expensive_function_cold/700/nested").unwrap().count();
+    assert_eq!(entries, 1);
+}
+
+#[cache_async(cache_root = "./cache/scoped/{arg}", invalidate_rate = 3600, cache_root_env = "DISK_CACHE_TEST_SCOPED", write_mode = "blocking")]
+async fn expensive_function_scoped(arg: i32) -> String {
+    format!("Hello {}", arg)
+}
+
+#[tokio::test]
+async fn check_scoped_cache_root(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/scoped").unwrap_or_default();
+    let mut root = disk_cache::scoped_cache_root().unwrap();
+    root.export("DISK_CACHE_TEST_SCOPED");
+    let dir = root.path().to_path_buf();
+    assert_eq!(expensive_function_scoped(710).await.unwrap(), "Hello 710");
+    // the entry lives in the temp directory only
+    assert!(dir.join("expensive_function_scoped/710/data.json").exists());
+    assert!(std::fs::metadata("./cache/scoped").is_err());
+    // until the guard is dropped
+    drop(root);
+    assert!(!dir.exists());
+    assert!(std::env::var("DISK_CACHE_TEST_SCOPED").is_err());
+}
+
+trait Greeting {
+    fn greet(&self) -> String;
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct EnglishGreeting {
+    name: String,
+}
+
+impl Greeting for EnglishGreeting {
+    fn greet(&self) -> String {
+        format!("Hello {}", self.name)
+    }
+}
+
+#[cache_async(cache_root = "./cache/concrete/{arg}", invalidate_rate = 3600, write_mode = "blocking", concrete = EnglishGreeting)]
+async fn expensive_function_concrete(arg: i32) -> Box<dyn Greeting> {
+    Box::new(EnglishGreeting { name: arg.to_string() })
+}
+
+#[cache_async(cache_root = "./cache/concrete_result/{arg}", invalidate_rate = 3600, write_mode = "blocking", concrete = EnglishGreeting)]
+async fn expensive_function_concrete_result(arg: i32) -> Result<Box<dyn Greeting>, String> {
+    if arg < 0 {
+        return Err("negative".to_string());
+    }
+    Ok(Box::new(EnglishGreeting { name: arg.to_string() }))
+}
+
+#[tokio::test]
+async fn check_concrete(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/concrete").unwrap_or_default();
+    std::fs::remove_dir_all("./cache/concrete_result").unwrap_or_default();
+    assert_eq!(expensive_function_concrete(710).await.unwrap().greet(), "Hello 710");
+    // the entry holds the concrete type, and is boxed again on a hit
+    let data = std::fs::read_to_string("./cache/concrete/expensive_function_concrete/710/data.json").unwrap();
+    assert_eq!(serde_json::from_str::<CacheEnvelope<EnglishGreeting>>(&data).unwrap().payload.name, "710");
+    assert!(expensive_function_concrete_is_cached(710).await.unwrap());
+    assert_eq!(expensive_function_concrete(710).await.unwrap().greet(), "Hello 710");
+    let (result, meta) = expensive_function_concrete_with_meta(710).await.unwrap();
+    assert_eq!(result.greet(), "Hello 710");
+    assert!(meta.hit);
+    // the Ok part of a Result is boxed the same way
+    assert_eq!(expensive_function_concrete_result(711).await.unwrap().unwrap().greet(), "Hello 711");
+    assert_eq!(expensive_function_concrete_result(711).await.unwrap().unwrap().greet(), "Hello 711");
+    assert_eq!(expensive_function_concrete_result(-1).await.unwrap().err().unwrap(), "negative");
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Versioned {
+    version: u32,
+    value: String,
+}
+
+fn is_current_version(versioned: &Versioned) -> bool {
+    versioned.version == 2
+}
+
+#[cache_async(cache_root = "./cache/validate/{arg}", invalidate_rate = 3600, write_mode = "blocking", validate = is_current_version)]
+async fn expensive_function_validate(arg: i32) -> Versioned {
+    Versioned { version: 2, value: format!("Hello {}", arg) }
+}
+
+#[tokio::test]
+async fn check_validate(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/validate").unwrap_or_default();
+    // an entry written by an older version is still fresh, but rejected
+    let path = expensive_function_validate_cache_path(720);
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    let outdated = envelope(Versioned { version: 1, value: "Outdated".to_string() });
+    std::fs::write(&path, serde_json::to_string(&outdated).unwrap()).unwrap();
+    let (result, meta) = expensive_function_validate_with_meta(720).await.unwrap();
+    assert_eq!(result.value, "Hello 720");
+    assert!(!meta.hit);
+    // and rewritten, so that the next call is a hit
+    let data = std::fs::read_to_string(&path).unwrap();
+    assert_eq!(serde_json::from_str::<CacheEnvelope<Versioned>>(&data).unwrap().payload.version, 2);
+    let (result, meta) = expensive_function_validate_with_meta(720).await.unwrap();
+    assert_eq!(result.value, "Hello 720");
+    assert!(meta.hit);
+}
+
+#[cache_async(cache_root = "./cache/min_compute_time/{arg}", invalidate_rate = 3600, write_mode = "blocking", min_compute_time_ms = 50)]
+async fn expensive_function_min_compute_time(arg: u64) -> String {
+    tokio::time::sleep(tokio::time::Duration::from_millis(arg)).await;
+    format!("Hello {}", arg)
+}
+
+#[tokio::test]
+async fn check_min_compute_time(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/min_compute_time").unwrap_or_default();
+    // a fast call is not worth caching
+    assert_eq!(expensive_function_min_compute_time(0).await.unwrap(), "Hello 0");
+    assert!(!expensive_function_min_compute_time_is_cached(0).await.unwrap());
+    // while a slow one is
+    assert_eq!(expensive_function_min_compute_time(100).await.unwrap(), "Hello 100");
+    assert!(expensive_function_min_compute_time_is_cached(100).await.unwrap());
+    expensive_function_min_compute_time_prime(1).await.unwrap();
+    assert!(!expensive_function_min_compute_time_is_cached(1).await.unwrap());
+}
+
+#[cache_async(cache_root = "./cache/format_version/{arg}", invalidate_rate = 3600, write_mode = "blocking", format_version = 2)]
+async fn expensive_function_format_version(arg: i32) -> String {
+    format!("Hello {}", arg)
+}
+
+#[tokio::test]
+async fn check_format_version(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/format_version").unwrap_or_default();
+    assert_eq!(expensive_function_format_version(760).await.unwrap(), "Hello 760");
+    // the entry starts with the header, followed by the envelope
+    let path = expensive_function_format_version_cache_path(760);
+    let data = std::fs::read(&path).unwrap();
+    assert_eq!(&data[..5], b"DKCH\x02");
+    assert_eq!(serde_json::from_slice::<CacheEnvelope<String>>(&data[5..]).unwrap().payload, "Hello 760");
+    assert!(expensive_function_format_version_is_cached(760).await.unwrap());
+    // an entry written by another version is a miss, and is replaced
+    let outdated = [b"DKCH\x01".as_slice(), &serde_json::to_vec(&envelope("Outdated")).unwrap()].concat();
+    std::fs::write(&path, outdated).unwrap();
+    assert!(!expensive_function_format_version_is_cached(760).await.unwrap());
+    let (result, meta) = expensive_function_format_version_with_meta(760).await.unwrap();
+    assert_eq!(result, "Hello 760");
+    assert!(!meta.hit);
+    assert_eq!(&std::fs::read(&path).unwrap()[..5], b"DKCH\x02");
+}
+
+#[test]
+fn check_stable_key_hash(){
+    // the directory of an entry with `key = "args"` is the XXH3 digest of its arguments, which never changes
+    let path = expensive_function_key_args_cache_path(1, "x".to_string());
+    let mut hasher = disk_cache::hash::Xxh3::default();
+    std::hash::Hash::hash(&1i32, &mut hasher);
+    std::hash::Hash::hash(&"x".to_string(), &mut hasher);
+    assert_eq!(path.parent().unwrap().file_name().unwrap().to_str().unwrap(), hasher.hex_digest());
+    assert_eq!(hasher.hex_digest(), format!("{:016x}", std::hash::Hasher::finish(&hasher)));
+    // integers and lengths are hashed the same way on every target
+    let mut hasher = disk_cache::hash::Xxh3::default();
+    std::hash::Hash::hash(&[1u32, 2], &mut hasher);
+    std::hash::Hash::hash(&vec![3usize], &mut hasher);
+    assert_eq!(hasher.hex_digest(), "6f1e6692d1431e25");
+}
+
+static PRIMES: [u32; 5] = [2, 3, 5, 7, 11];
+
+#[cache_async(cache_root = "./cache/owned_as/{arg}", invalidate_rate = 3600, write_mode = "blocking", owned_as = Vec<u32>)]
+async fn expensive_function_owned_as(arg: usize) -> &'static [u32] {
+    &PRIMES[..arg]
+}
+
+#[tokio::test]
+async fn check_owned_as(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/owned_as").unwrap_or_default();
+    // the slice is returned, and cached, as a Vec
+    let result: Vec<u32> = expensive_function_owned_as(3).await.unwrap();
+    assert_eq!(result, vec![2, 3, 5]);
+    let data = std::fs::read_to_string("./cache/owned_as/expensive_function_owned_as/3/data.json").unwrap();
+    assert_eq!(serde_json::from_str::<CacheEnvelope<Vec<u32>>>(&data).unwrap().payload, vec![2, 3, 5]);
+    let (result, meta) = expensive_function_owned_as_with_meta(3).await.unwrap();
+    assert_eq!(result, vec![2, 3, 5]);
+    assert!(meta.hit);
+}
+
+#[cache_async(cache_root = "./cache/clear_all/users/{arg}", invalidate_rate = 3600, write_mode = "blocking")]
+async fn expensive_function_clear_all(arg: i32) -> String {
+    format!("Hello {}", arg)
+}
+
+#[cache_async(cache_root = "./cache/clear_all/users/{arg}", invalidate_rate = 3600, write_mode = "blocking")]
+async fn expensive_function_clear_all_sibling(arg: i32) -> String {
+    format!("Hi {}", arg)
+}
+
+#[tokio::test]
+async fn check_clear_all(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/clear_all").unwrap_or_default();
+    for arg in [780, 781] {
+        expensive_function_clear_all(arg).await.unwrap();
+        expensive_function_clear_all_sibling(arg).await.unwrap();
+    }
+    // every entry of the function is removed, up to its first placeholder
+    expensive_function_clear_all_clear_all().await.unwrap();
+    assert!(!expensive_function_clear_all_is_cached(780).await.unwrap());
+    assert!(!expensive_function_clear_all_is_cached(781).await.unwrap());
+    assert!(std::fs::metadata("./cache/clear_all/users/expensive_function_clear_all").is_err());
+    // but not those of another function sharing its cache root
+    assert!(expensive_function_clear_all_sibling_is_cached(780).await.unwrap());
+    assert!(expensive_function_clear_all_sibling_is_cached(781).await.unwrap());
+    // clearing nothing is fine
+    expensive_function_clear_all_clear_all().await.unwrap();
+}
+
+#[cache_async(cache_root = "./cache/pretty/{arg}", invalidate_rate = 3600, pretty = true, write_mode = "blocking")]
+async fn expensive_function_pretty(arg: i32) -> Vec<String> {
+    vec![format!("Hello {}", arg)]
+}
+
+#[tokio::test]
+async fn check_pretty(){
+    // clear the cache
+    let cache_path = "./cache/pretty/expensive_function_pretty/790";
+    std::fs::remove_dir_all(cache_path).unwrap_or_default();
+    assert_eq!(expensive_function_pretty(790).await.unwrap(), ["Hello 790"]);
+    // the entry is spread over several lines, and reads back the same
+    let data = std::fs::read_to_string(format!("{cache_path}/data.json")).unwrap();
+    assert!(data.lines().count() > 1);
+    assert_eq!(serde_json::from_str::<CacheEnvelope<Vec<String>>>(&data).unwrap().payload, ["Hello 790"]);
+    assert!(expensive_function_pretty_is_cached(790).await.unwrap());
+    assert_eq!(expensive_function_pretty(790).await.unwrap(), ["Hello 790"]);
+}
+
+#[cache_async(cache_root = "./cache/overlay/{arg}", invalidate_rate = 3600, read_fallback = "./cache/base", write_mode = "blocking")]
+async fn expensive_function_read_fallback(arg: i32) -> String {
+    format!("computed {}", arg)
+}
+
+#[tokio::test]
+async fn check_read_fallback(){
+    // clear the cache, and seed the fallback root with an entry
+    std::fs::remove_dir_all("./cache/overlay/expensive_function_read_fallback").unwrap_or_default();
+    std::fs::remove_dir_all("./cache/base/expensive_function_read_fallback").unwrap_or_default();
+    std::fs::create_dir_all("./cache/base/expensive_function_read_fallback/800").unwrap();
+    std::fs::write("./cache/base/expensive_function_read_fallback/800/data.json", serde_json::to_vec(&envelope("seeded 800")).unwrap()).unwrap();
+    // a miss below the cache root reads the seeded entry, without copying it
+    assert!(expensive_function_read_fallback_is_cached(800).await.unwrap());
+    assert_eq!(expensive_function_read_fallback(800).await.unwrap(), "seeded 800");
+    assert!(!std::path::Path::new("./cache/overlay/expensive_function_read_fallback/800/data.json").exists());
+    // other keys are computed and written below the cache root only
+    assert_eq!(expensive_function_read_fallback(801).await.unwrap(), "computed 801");
+    assert!(std::path::Path::new("./cache/overlay/expensive_function_read_fallback/801/data.json").exists());
+    assert!(!std::path::Path::new("./cache/base/expensive_function_read_fallback/801").exists());
+    // an expired seeded entry is recomputed, and the fresh one overrides it
+    let expired = CacheEnvelope { written_at: 0, payload: "seeded 802" };
+    std::fs::create_dir_all("./cache/base/expensive_function_read_fallback/802").unwrap();
+    std::fs::write("./cache/base/expensive_function_read_fallback/802/data.json", serde_json::to_vec(&expired).unwrap()).unwrap();
+    assert!(!expensive_function_read_fallback_is_cached(802).await.unwrap());
+    assert_eq!(expensive_function_read_fallback(802).await.unwrap(), "computed 802");
+    assert!(expensive_function_read_fallback_is_cached(802).await.unwrap());
+}
+
+#[cache_async(cache_root = "./cache/auto/{arg}", invalidate_rate = 3600, format = "auto", write_mode = "blocking")]
+async fn expensive_function_auto(arg: i32) -> String {
+    format!("computed {}", arg)
+}
+
+#[tokio::test]
+async fn check_format_auto(){
+    // clear the cache, and leave entries written in other formats
+    let cache_path = "./cache/auto/expensive_function_auto";
+    std::fs::remove_dir_all(cache_path).unwrap_or_default();
+    std::fs::create_dir_all(format!("{cache_path}/810")).unwrap();
+    std::fs::write(format!("{cache_path}/810/data.bin"), bincode::serialize(&envelope("bincode 810")).unwrap()).unwrap();
+    std::fs::create_dir_all(format!("{cache_path}/811")).unwrap();
+    std::fs::write(format!("{cache_path}/811/data.yaml"), serde_yaml::to_string(&envelope("yaml 811")).unwrap()).unwrap();
+    // each is read with the format of its extension
+    assert!(expensive_function_auto_is_cached(810).await.unwrap());
+    assert_eq!(expensive_function_auto(810).await.unwrap(), "bincode 810");
+    assert_eq!(expensive_function_auto(811).await.unwrap(), "yaml 811");
+    // and new entries are written as JSON
+    assert_eq!(expensive_function_auto(812).await.unwrap(), "computed 812");
+    let data = std::fs::read_to_string(format!("{cache_path}/812/data.json")).unwrap();
+    assert_eq!(serde_json::from_str::<CacheEnvelope<String>>(&data).unwrap().payload, "computed 812");
+    assert!(expensive_function_auto_is_cached(812).await.unwrap());
+}
+
+// `spawn = "local"` spawns onto a tokio `LocalSet`, so that it is rejected with the `async-std` runtime
+#[cfg(not(feature = "async-std"))]
+#[cache_async(cache_root = "./cache/spawn_local/{arg}", invalidate_rate = 3600, stale_while_revalidate = true, spawn = "local")]
+async fn expensive_function_spawn_local(arg: std::rc::Rc<i32>) -> String {
+    format!("Hello {}", arg)
+}
+
+#[cfg(not(feature = "async-std"))]
+#[tokio::test]
+async fn check_spawn_local(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/spawn_local").unwrap_or_default();
+    // the argument is not `Send`, so the refresh can only run on the local set
+    let local = tokio::task::LocalSet::new();
+    local.run_until(async {
+        assert_eq!(expensive_function_spawn_local(std::rc::Rc::new(820)).await.unwrap(), "Hello 820");
+        // sleep to let the cache be written
+        tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+        assert!(expensive_function_spawn_local_is_cached(std::rc::Rc::new(820)).await.unwrap());
+        assert_eq!(expensive_function_spawn_local(std::rc::Rc::new(820)).await.unwrap(), "Hello 820");
+    }).await;
+}
+
+#[cache_async(cache_root = "./cache/spawn_inline/{arg}", invalidate_rate = 3600, spawn = "inline")]
+async fn expensive_function_spawn_inline(arg: i32) -> String {
+    format!("Hello {}", arg)
+}
+
+#[tokio::test]
+async fn check_spawn_inline(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/spawn_inline").unwrap_or_default();
+    assert_eq!(expensive_function_spawn_inline(821).await.unwrap(), "Hello 821");
+    // the write completed before the function returned
+    assert!(expensive_function_spawn_inline_is_cached(821).await.unwrap());
+}
+
+#[cache_async(cache_root = "./cache/never/{arg}", invalidate_rate = "never", memory_capacity = 4)]
+async fn expensive_function_never(arg: i32) -> String {
+    format!("computed {}", arg)
+}
+
+#[tokio::test]
+async fn check_invalidate_never(){
+    // clear the cache, and leave an entry written long ago
+    let cache_path = "./cache/never/expensive_function_never/830";
+    std::fs::remove_dir_all(cache_path).unwrap_or_default();
+    std::fs::create_dir_all(cache_path).unwrap();
+    let ancient = CacheEnvelope { written_at: 0, payload: "ancient 830" };
+    std::fs::write(format!("{cache_path}/data.json"), serde_json::to_vec(&ancient).unwrap()).unwrap();
+    // it is still served, from disk and then from memory
+    assert!(expensive_function_never_is_cached(830).await.unwrap());
+    assert_eq!(expensive_function_never(830).await.unwrap(), "ancient 830");
+    assert_eq!(expensive_function_never(830).await.unwrap(), "ancient 830");
+}
+
+#[cache_async(cache_root = "./cache/body/{arg}", cache_id = "versioned", invalidate_rate = 3600, key_includes_body = true, write_mode = "blocking")]
+async fn expensive_function_body_v1(arg: i32) -> String {
+    format!("v1 {}", arg)
+}
+
+#[cache_async(cache_root = "./cache/body/{arg}", cache_id = "versioned", invalidate_rate = 3600, key_includes_body = true, write_mode = "blocking")]
+async fn expensive_function_body_v2(arg: i32) -> String {
+    format!("v2 {}", arg)
+}
+
+#[tokio::test]
+async fn check_key_includes_body(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/body").unwrap_or_default();
+    // the hash of the body sits between the fixed directories and the interpolated ones
+    let path = expensive_function_body_v1_cache_path(840);
+    let hash = path.parent().unwrap().parent().unwrap().file_name().unwrap().to_str().unwrap().to_string();
+    assert_eq!(hash.len(), 16);
+    assert!(path.starts_with(format!("./cache/body/versioned/{hash}/840")));
+    // the same entry of two versions of the body is kept apart
+    assert_eq!(expensive_function_body_v1(840).await.unwrap(), "v1 840");
+    assert_eq!(expensive_function_body_v2(840).await.unwrap(), "v2 840");
+    assert_eq!(expensive_function_body_v1(840).await.unwrap(), "v1 840");
+    assert_ne!(path, expensive_function_body_v2_cache_path(840));
+    // and clearing them all removes every version
+    expensive_function_body_v1_clear_all().await.unwrap();
+    assert!(!expensive_function_body_v2_is_cached(840).await.unwrap());
+}
+
+#[cache_async(cache_root = "./cache/deterministic/{arg}", invalidate_rate = 3600, deterministic = true, write_mode = "blocking")]
+async fn expensive_function_deterministic(arg: u32) -> std::collections::HashMap<String, u32> {
+    (0..arg).map(|i| (format!("key {:02}", i), i)).collect()
+}
+
+#[tokio::test]
+async fn check_deterministic(){
+    // clear the cache
+    let cache_path = "./cache/deterministic/expensive_function_deterministic/20";
+    std::fs::remove_dir_all(cache_path).unwrap_or_default();
+    let result = expensive_function_deterministic(20).await.unwrap();
+    // the keys are written in order, whatever the order of the map
+    let data = std::fs::read_to_string(format!("{cache_path}/data.json")).unwrap();
+    let keys: Vec<usize> = (0..20).map(|i| data.find(&format!("\"key {:02}\"", i)).unwrap()).collect();
+    assert!(keys.windows(2).all(|pair| pair[0] < pair[1]));
+    assert_eq!(expensive_function_deterministic(20).await.unwrap(), result);
+}
+
+#[cache_async(cache_root = "./cache/integrity/{arg}", invalidate_rate = 3600, write_mode = "blocking", integrity = "crc32")]
+async fn expensive_function_integrity(arg: i32) -> String {
+    format!("Hello {}", arg)
+}
+
+#[tokio::test]
+async fn check_integrity(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/integrity").unwrap_or_default();
+    assert_eq!(expensive_function_integrity(780).await.unwrap(), "Hello 780");
+    // the entry ends with the CRC-32 of the envelope before it
+    let path = expensive_function_integrity_cache_path(780);
+    let data = std::fs::read(&path).unwrap();
+    let (body, footer) = data.split_at(data.len() - 4);
+    assert_eq!(serde_json::from_slice::<CacheEnvelope<String>>(body).unwrap().payload, "Hello 780");
+    assert_eq!(footer, <disk_cache::integrity::Crc32 as disk_cache::integrity::Checksum>::checksum(body));
+    assert_eq!(<disk_cache::integrity::Crc32 as disk_cache::integrity::Checksum>::checksum(b"123456789"), 0xCBF4_3926u32.to_le_bytes());
+    assert!(expensive_function_integrity_is_cached(780).await.unwrap());
+    // a corrupted payload that still deserializes is a miss, and is recomputed
+    let corrupted = [serde_json::to_vec(&envelope("Hello 781")).unwrap().as_slice(), footer].concat();
+    std::fs::write(&path, corrupted).unwrap();
+    assert!(!expensive_function_integrity_is_cached(780).await.unwrap());
+    let (result, meta) = expensive_function_integrity_with_meta(780).await.unwrap();
+    assert_eq!(result, "Hello 780");
+    assert!(!meta.hit);
+    let (result, meta) = expensive_function_integrity_with_meta(780).await.unwrap();
+    assert_eq!(result, "Hello 780");
+    assert!(meta.hit);
+}
+
+const SHARED_CONFIG: disk_cache::CacheConfig = disk_cache::CacheConfig::new("./cache/config").invalidate_rate(60);
+
+#[cache_async(config = SHARED_CONFIG, key = "args", write_mode = "blocking")]
+async fn expensive_function_config(arg: i32) -> String {
+    format!("Hello {}", arg)
+}
+
+#[cache_async(config = SHARED_CONFIG, cache_root = "./cache/config_override/{arg}", invalidate_rate = 3600, write_mode = "blocking")]
+async fn expensive_function_config_override(arg: i32) -> String {
+    format!("Hello {}", arg)
+}
+
+#[tokio::test]
+async fn check_config(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/config").unwrap_or_default();
+    std::fs::remove_dir_all("./cache/config_override").unwrap_or_default();
+    // the entries are stored below the root of the configuration, and expire after its invalidate rate
+    assert_eq!(expensive_function_config(790).await.unwrap(), "Hello 790");
+    let path = expensive_function_config_cache_path(790);
+    assert!(path.starts_with("./cache/config/expensive_function_config"));
+    assert!(path.exists());
+    let two_minutes_old = CacheEnvelope { written_at: chrono::Utc::now().timestamp_millis() - 2 * 60 * 1000, payload: "Old" };
+    std::fs::write(&path, serde_json::to_vec(&two_minutes_old).unwrap()).unwrap();
+    assert!(!expensive_function_config_is_cached(790).await.unwrap());
+    assert_eq!(expensive_function_config(790).await.unwrap(), "Hello 790");
+    // attributes of the function override those of the configuration
+    assert_eq!(expensive_function_config_override(790).await.unwrap(), "Hello 790");
+    let path = expensive_function_config_override_cache_path(790);
+    assert_eq!(path, std::path::PathBuf::from("./cache/config_override/expensive_function_config_override/790/data.json"));
+    std::fs::write(&path, serde_json::to_vec(&two_minutes_old).unwrap()).unwrap();
+    assert_eq!(expensive_function_config_override(790).await.unwrap(), "Old");
+    // the whole tree below the root of the configuration is removed
+    expensive_function_config_clear_all().await.unwrap();
+    assert!(!std::path::Path::new("./cache/config/expensive_function_config").exists());
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct ProfileV1 {
+    name: String,
+}
+
+#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+struct ProfileV2 {
+    name: String,
+    tags: Vec<String>,
+}
+
+impl From<ProfileV1> for ProfileV2 {
+    fn from(profile: ProfileV1) -> Self {
+        ProfileV2 { name: profile.name, tags: Vec::new() }
+    }
+}
+
+#[cache_async(cache_root = "./cache/read_as/{arg}", invalidate_rate = 3600, write_mode = "blocking", read_as = ProfileV1)]
+async fn expensive_function_read_as(arg: i32) -> ProfileV2 {
+    ProfileV2 { name: format!("Hello {}", arg), tags: vec![String::from("new")] }
+}
+
+#[tokio::test]
+async fn check_read_as(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/read_as").unwrap_or_default();
+    // an entry of the old type is converted rather than recomputed
+    let path = expensive_function_read_as_cache_path(800);
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(&path, serde_json::to_vec(&envelope(ProfileV1 { name: String::from("Old") })).unwrap()).unwrap();
+    let result = expensive_function_read_as(800).await.unwrap();
+    assert_eq!(result, ProfileV2 { name: String::from("Old"), tags: Vec::new() });
+    // an entry of the cached type keeps every field
+    expensive_function_read_as_clear_all().await.unwrap();
+    expensive_function_read_as(800).await.unwrap();
+    let (result, meta) = expensive_function_read_as_with_meta(800).await.unwrap();
+    assert!(meta.hit);
+    assert_eq!(result.tags, ["new"]);
+    // an entry of neither type is corrupt
+    std::fs::write(&path, b"[1, 2, 3]").unwrap();
+    let (result, meta) = expensive_function_read_as_with_meta(800).await.unwrap();
+    assert!(!meta.hit);
+    assert_eq!(result.name, "Hello 800");
+}
+
+static EVENTS: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
+
+fn record_event(event: disk_cache::CacheEvent) {
+    let event = match event {
+        disk_cache::CacheEvent::Hit { .. } => "hit",
+        disk_cache::CacheEvent::Miss => "miss",
+        disk_cache::CacheEvent::WriteOk => "write ok",
+        disk_cache::CacheEvent::WriteErr(_) => "write error",
+        _ => "other",
+    };
+    EVENTS.lock().unwrap().push(event.to_string());
+}
+
+#[cache(cache_root = "./cache/on_event/{arg}", invalidate_rate = 3600, write_mode = "blocking", on_error = "fallback", on_event = record_event)]
+fn expensive_function_on_event(arg: i32) -> i32 {
+    arg
+}
+
+#[test]
+fn check_on_event(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/on_event").unwrap_or_default();
+    assert_eq!(expensive_function_on_event(810), 810);
+    assert_eq!(expensive_function_on_event(810), 810);
+    // a file in place of the cache directory makes the write fail
+    std::fs::write("./cache/on_event/expensive_function_on_event/811", "").unwrap();
+    assert_eq!(expensive_function_on_event(811), 811);
+    assert_eq!(*EVENTS.lock().unwrap(), ["miss", "write ok", "hit", "miss", "write error"]);
+}
+
+type AliasedResult = Result<String, std::io::Error>;
+
+static ALIAS_CALLS: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+#[cache_async(cache_root = "./cache/is_result/{arg}", invalidate_rate = 3600, write_mode = "blocking", is_result = true)]
+async fn expensive_function_is_result(arg: i32) -> AliasedResult {
+    ALIAS_CALLS.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+    if arg < 0 {
+        return Err(std::io::Error::other("negative"));
+    }
+    Ok(format!("Hello {}", arg))
+}
+
+#[tokio::test]
+async fn check_is_result(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/is_result").unwrap_or_default();
+    // the alias is cached like the `Result` it stands for: only `Ok` values are stored
+    assert_eq!(expensive_function_is_result(820).await.unwrap().unwrap(), "Hello 820");
+    let data = std::fs::read_to_string(expensive_function_is_result_cache_path(820)).unwrap();
+    assert_eq!(serde_json::from_str::<CacheEnvelope<String>>(&data).unwrap().payload, "Hello 820");
+    assert_eq!(expensive_function_is_result(820).await.unwrap().unwrap(), "Hello 820");
+    assert!(expensive_function_is_result(-820).await.unwrap().is_err());
+    assert!(!expensive_function_is_result_is_cached(-820).await.unwrap());
+    assert_eq!(ALIAS_CALLS.load(std::sync::atomic::Ordering::SeqCst), 2);
+}
+
+#[cache(invalidate_rate = 3600)]
+fn expensive_function_default_root(arg: i32) -> i32 {
+    arg
+}
+
+#[test]
+fn check_default_root(){
+    // without `cache_root`, the root is looked up where the function runs
+    let path = expensive_function_default_root_cache_path(850);
+    assert_eq!(path, std::path::Path::new(&disk_cache::file::default_cache_root()).join("expensive_function_default_root/data.json"));
+}
+
+#[cache(cache_root = "~/.cache/disk_cache_tests/{arg}", invalidate_rate = 3600)]
+fn expensive_function_home_root(arg: i32) -> i32 {
+    arg
+}
+
+#[test]
+fn check_home_root(){
+    // a leading `~` is the home directory where the function runs
+    let path = expensive_function_home_root_cache_path(860);
+    let root = disk_cache::file::expand_home("~/.cache/disk_cache_tests");
+    assert_eq!(path, std::path::Path::new(&root).join("expensive_function_home_root/860/data.json"));
+}
+
+#[cache_async(cache_root = "./cache/braces/{{fixed}}/{arg}", invalidate_rate = 3600, write_mode = "blocking")]
+async fn expensive_function_braces(arg: i32) -> i32 {
+    arg
+}
+
+#[tokio::test]
+async fn check_escaped_braces(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/braces").unwrap_or_default();
+    // an escaped brace is part of the fixed directories, not the start of a placeholder
+    let path = expensive_function_braces_cache_path(870);
+    assert_eq!(path, std::path::PathBuf::from("./cache/braces/{fixed}/expensive_function_braces/870/data.json"));
+    assert_eq!(expensive_function_braces(870).await.unwrap(), 870);
+    assert!(path.exists());
+    expensive_function_braces_clear_all().await.unwrap();
+    assert!(std::fs::metadata("./cache/braces/{fixed}/expensive_function_braces").is_err());
+}
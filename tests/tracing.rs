@@ -0,0 +1,51 @@
+#![cfg(feature = "tracing")]
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use disk_cache::cache;
+use disk_cache::tracing::{span, subscriber, Event, Metadata, Subscriber};
+
+/// Counts the events emitted by the generated code, which go through the `tracing` re-exported by `disk_cache`.
+#[derive(Clone, Default)]
+struct Events(Arc<AtomicUsize>);
+
+impl Subscriber for Events {
+    fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, _span: &span::Attributes<'_>) -> span::Id {
+        span::Id::from_u64(1)
+    }
+
+    fn record(&self, _span: &span::Id, _values: &span::Record<'_>) {}
+
+    fn record_follows_from(&self, _span: &span::Id, _follows: &span::Id) {}
+
+    fn event(&self, _event: &Event<'_>) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn enter(&self, _span: &span::Id) {}
+
+    fn exit(&self, _span: &span::Id) {}
+}
+
+#[cache(cache_root = "./cache/tracing/{arg}", invalidate_rate = 3600, write_mode = "blocking")]
+fn expensive_function_tracing(arg: i32) -> i32 {
+    arg
+}
+
+#[test]
+fn check_tracing(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/tracing/expensive_function_tracing/880").unwrap_or_default();
+    let events = Events::default();
+    subscriber::with_default(events.clone(), || {
+        expensive_function_tracing(880).unwrap();
+        expensive_function_tracing(880).unwrap();
+    });
+    // a miss, the write, and then a hit
+    assert_eq!(events.0.load(Ordering::SeqCst), 3);
+}
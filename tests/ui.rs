@@ -0,0 +1,6 @@
+// compile errors of the user's code and of the attributes, checked against the `.stderr` file next to each case
+#[test]
+fn ui(){
+    let cases = trybuild::TestCases::new();
+    cases.compile_fail("tests/ui/*.rs");
+}
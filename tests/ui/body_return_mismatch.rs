@@ -0,0 +1,11 @@
+use disk_cache::cache_async;
+
+#[cache_async(cache_root = "./cache/ui/{arg}")]
+async fn body_return_mismatch(arg: i32) -> Vec<i32> {
+    if arg < 0 {
+        return Vec::new();
+    }
+    arg
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use disk_cache::cache;
+
+#[cache(cache_root = "./cache/ui/{arg}")]
+fn body_type_error(arg: i32) -> String {
+    let length: usize = "not a number";
+    format!("{} {}", arg, length)
+}
+
+fn main() {}
@@ -0,0 +1,8 @@
+use disk_cache::cache;
+
+#[cache(cache_root = "./cache/ui/{arg}", invalidate_rate = "99999999999999999d")]
+fn duration_too_large(arg: i32) -> String {
+    format!("{}", arg)
+}
+
+fn main() {}
@@ -0,0 +1,8 @@
+use disk_cache::cache;
+
+#[cache(cache_root = "./cache/ui/{arg")]
+fn invalid_format_string(arg: i32) -> String {
+    format!("{}", arg)
+}
+
+fn main() {}
@@ -0,0 +1,8 @@
+use disk_cache::cache;
+
+#[cache(cache_root = "./cache/ui/{arg}")]
+fn no_return_type(arg: i32) {
+    println!("{}", arg);
+}
+
+fn main() {}
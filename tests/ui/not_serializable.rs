@@ -0,0 +1,12 @@
+use disk_cache::cache;
+
+struct NotSerializable {
+    value: i32,
+}
+
+#[cache(cache_root = "./cache/ui/{arg}")]
+fn not_serializable(arg: i32) -> NotSerializable {
+    NotSerializable { value: arg }
+}
+
+fn main() {}
@@ -0,0 +1,8 @@
+use disk_cache::cache;
+
+#[cache(cache_root = "./cache/ui/{arg}", format = "xml")]
+fn unknown_attribute_value(arg: i32) -> String {
+    format!("{}", arg)
+}
+
+fn main() {}
@@ -0,0 +1,29 @@
+#![cfg(feature = "warm")]
+
+use disk_cache::cache_async;
+
+static CALLS: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+#[cache_async(cache_root = "./cache/warm/{arg}", invalidate_rate = 3600)]
+async fn expensive_function_warm(arg: i32) -> String {
+    CALLS.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+    format!("Hello {}", arg)
+}
+
+#[tokio::test]
+async fn check_warm_many(){
+    // clear the cache
+    std::fs::remove_dir_all("./cache/warm").unwrap_or_default();
+    let results = disk_cache::warm_many(0..20, 4, expensive_function_warm_prime).await;
+    assert_eq!(results.len(), 20);
+    assert!(results.iter().all(|result| result.is_ok()));
+    for arg in 0..20 {
+        assert!(expensive_function_warm_is_cached(arg).await.unwrap());
+    }
+    // warming again finds every entry
+    disk_cache::warm_many(0..20, 4, expensive_function_warm_prime).await;
+    assert_eq!(CALLS.load(std::sync::atomic::Ordering::SeqCst), 20);
+    // the results follow the order of the arguments
+    let results = disk_cache::warm_many([1, 2, 3], 2, |arg| async move { if arg == 2 { Err(arg) } else { Ok(()) } }).await;
+    assert_eq!(results, [Ok(()), Err(2), Ok(())]);
+}